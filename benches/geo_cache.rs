@@ -0,0 +1,94 @@
+//! Benchmarks for the geocoding cache hit path.
+//!
+//! Run with `cargo bench --bench geo_cache`.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use weather::cache::{CacheBackend, CacheEntry, CityLocks, GeoCache};
+use weather::LatLong;
+
+const CITIES: usize = 1_000;
+
+fn populated_cache(runtime: &tokio::runtime::Runtime) -> Arc<GeoCache> {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let cache = Arc::new(GeoCache::new(db));
+    runtime.block_on(async {
+        for i in 0..CITIES {
+            let lat_long = LatLong {
+                latitude: i as f64 / 100.0,
+                longitude: i as f64 / 100.0,
+            };
+            cache
+                .set(&format!("city-{i}"), CacheEntry::new(lat_long))
+                .await
+                .unwrap();
+            // Promote into the memory tier, as a served request would.
+            cache.get(&format!("city-{i}")).await.unwrap();
+        }
+    });
+    cache
+}
+
+fn hit_path(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let cache = populated_cache(&runtime);
+
+    c.bench_function("get/hit", |b| {
+        let mut i = 0;
+        b.to_async(&runtime).iter(|| {
+            i = (i + 1) % CITIES;
+            let cache = Arc::clone(&cache);
+            async move { cache.get(&format!("city-{i}")).await.unwrap() }
+        });
+    });
+}
+
+fn city_locks(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let locks = CityLocks::default();
+
+    c.bench_function("city_locks/uncontended", |b| {
+        b.to_async(&runtime).iter(|| async {
+            drop(locks.lock("London").await);
+        });
+    });
+}
+
+/// Many tasks reading at once, spread over all cities so that sharding
+/// matters, or all on one city to measure contention on a single shard.
+fn concurrent_hits(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+    let cache = populated_cache(&runtime);
+    let mut group = c.benchmark_group("get/concurrent");
+
+    for (name, spread) in [("all_cities", CITIES), ("one_city", 1)] {
+        for tasks in [8, 64] {
+            group.bench_with_input(BenchmarkId::new(name, tasks), &tasks, |b, &tasks| {
+                b.to_async(&runtime).iter(|| {
+                    let cache = Arc::clone(&cache);
+                    async move {
+                        let handles: Vec<_> = (0..tasks)
+                            .map(|t| {
+                                let cache = Arc::clone(&cache);
+                                tokio::spawn(async move {
+                                    for i in 0..100 {
+                                        let city = format!("city-{}", (t * 100 + i) % spread);
+                                        cache.get(&city).await.unwrap();
+                                    }
+                                })
+                            })
+                            .collect();
+                        for handle in handles {
+                            handle.await.unwrap();
+                        }
+                    }
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, hit_path, city_locks, concurrent_hits);
+criterion_main!(benches);
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use base64::{engine::general_purpose, Engine as _};
+
+use crate::{ApiError, AppState};
+
+/// Who made an authenticated request.
+pub struct Principal {
+    name: String,
+}
+
+/// Checks a request's credentials under one authentication scheme.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, parts: &Parts) -> Result<Principal, ApiError>;
+}
+
+/// `Authorization: Basic` with the fixed `forecast:forecast` login.
+pub struct BasicAuth;
+
+impl Authenticator for BasicAuth {
+    fn authenticate(&self, parts: &Parts) -> Result<Principal, ApiError> {
+        let credentials = authorization(parts)
+            .and_then(|header| header.strip_prefix("Basic "))
+            .ok_or(ApiError::Unauthorized)?;
+        let decoded = general_purpose::STANDARD
+            .decode(credentials)
+            .map_err(|_| ApiError::Unauthorized)?;
+        if !constant_time_eq(&decoded, b"forecast:forecast") {
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(Principal {
+            name: "forecast".to_string(),
+        })
+    }
+}
+
+/// `Authorization: Bearer <AUTH_TOKEN>`.
+struct BearerAuth {
+    token: String,
+}
+
+impl Authenticator for BearerAuth {
+    fn authenticate(&self, parts: &Parts) -> Result<Principal, ApiError> {
+        match authorization(parts).and_then(|header| header.strip_prefix("Bearer ")) {
+            Some(token) if constant_time_eq(token.as_bytes(), self.token.as_bytes()) => {
+                Ok(Principal {
+                    name: "bearer token".to_string(),
+                })
+            }
+            _ => Err(ApiError::Unauthorized),
+        }
+    }
+}
+
+/// `X-API-Key: <API_KEY>`.
+struct ApiKeyAuth {
+    key: String,
+}
+
+impl Authenticator for ApiKeyAuth {
+    fn authenticate(&self, parts: &Parts) -> Result<Principal, ApiError> {
+        match parts.headers.get("X-API-Key").and_then(|h| h.to_str().ok()) {
+            Some(key) if constant_time_eq(key.as_bytes(), self.key.as_bytes()) => Ok(Principal {
+                name: "API key".to_string(),
+            }),
+            _ => Err(ApiError::Unauthorized),
+        }
+    }
+}
+
+fn authorization(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+}
+
+/// Compares without returning early, so response times don't reveal how much
+/// of a guessed secret was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The scheme named by `AUTH_SCHEME`: `basic` (the default), `bearer` with
+/// `AUTH_TOKEN`, or `api_key` with `API_KEY`.
+pub fn authenticator_from_env() -> Result<Box<dyn Authenticator>, String> {
+    let secret = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| format!("This AUTH_SCHEME needs {name} to be set"))
+    };
+    match std::env::var("AUTH_SCHEME").as_deref() {
+        Err(_) | Ok("basic") => Ok(Box::new(BasicAuth)),
+        Ok("bearer") => Ok(Box::new(BearerAuth {
+            token: secret("AUTH_TOKEN")?,
+        })),
+        Ok("api_key") => Ok(Box::new(ApiKeyAuth {
+            key: secret("API_KEY")?,
+        })),
+        Ok(other) => Err(format!(
+            "Unknown AUTH_SCHEME {other:?}, expected basic, bearer or api_key"
+        )),
+    }
+}
+
+/// An authenticated request, under whichever scheme is configured.
+pub(crate) struct User;
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for User {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let principal = state.authenticator.authenticate(parts)?;
+        tracing::debug!("{} authenticated as {}", parts.uri.path(), principal.name);
+        Ok(User)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+
+    /// State for tests that never reach the database.
+    fn state_without_database(authenticator: Box<dyn Authenticator>) -> Arc<AppState> {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        Arc::new(AppState {
+            authenticator,
+            ..AppState::for_tests(pool)
+        })
+    }
+
+    /// Runs a request with `header` through the [`User`] extractor.
+    async fn authenticate(
+        authenticator: Box<dyn Authenticator>,
+        (name, value): (&str, &str),
+    ) -> Result<User, ApiError> {
+        let request = axum::http::Request::builder()
+            .uri("/stats")
+            .header(name, value)
+            .body(())
+            .unwrap();
+        let (mut parts, ()) = request.into_parts();
+        User::from_request_parts(&mut parts, &state_without_database(authenticator)).await
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!("Basic {}", general_purpose::STANDARD.encode(credentials))
+    }
+
+    #[tokio::test]
+    async fn basic_auth_accepts_the_fixed_login() {
+        let header = basic("forecast:forecast");
+
+        let result = authenticate(Box::new(BasicAuth), ("Authorization", &header)).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn basic_auth_rejects_a_wrong_password() {
+        let header = basic("forecast:guess");
+
+        let result = authenticate(Box::new(BasicAuth), ("Authorization", &header)).await;
+
+        assert!(matches!(result, Err(ApiError::Unauthorized)));
+    }
+
+    fn bearer() -> Box<dyn Authenticator> {
+        Box::new(BearerAuth {
+            token: "s3cret".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn bearer_auth_accepts_the_configured_token() {
+        let result = authenticate(bearer(), ("Authorization", "Bearer s3cret")).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn bearer_auth_rejects_other_tokens_and_schemes() {
+        for header in [
+            "Bearer s3cre",
+            "Bearer s3cret2",
+            basic("forecast:forecast").as_str(),
+        ] {
+            let result = authenticate(bearer(), ("Authorization", header)).await;
+
+            assert!(matches!(result, Err(ApiError::Unauthorized)), "{header}");
+        }
+    }
+
+    fn api_key() -> Box<dyn Authenticator> {
+        Box::new(ApiKeyAuth {
+            key: "k3y".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn api_key_auth_accepts_the_configured_key() {
+        let result = authenticate(api_key(), ("X-API-Key", "k3y")).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn api_key_auth_rejects_other_keys_and_headers() {
+        for header in [("X-API-Key", "k3"), ("Authorization", "Bearer k3y")] {
+            let result = authenticate(api_key(), header).await;
+
+            assert!(matches!(result, Err(ApiError::Unauthorized)), "{header:?}");
+        }
+    }
+}
@@ -43,6 +43,8 @@ struct LatLong {
     longitude: f64,
 }
 
+// Only printed with `{:?}` at this step, so the fields are never read.
+#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 struct WeatherResponse {
     latitude: f64,
@@ -51,6 +53,7 @@ struct WeatherResponse {
     hourly: Hourly,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 struct Hourly {
     time: Vec<String>,
@@ -65,7 +68,7 @@ async fn fetch_lat_long(city: &str) -> Result<LatLong, Box<dyn std::error::Error
     let response = reqwest::get(&url).await?.json::<GeoResponse>().await?;
     response
         .results
-        .get(0)
+        .first()
         .cloned()
         .ok_or_else(|| "No results found".into())
 }
@@ -4,19 +4,26 @@ use axum::{
     Router,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{migrate::Migrator, PgPool};
 use std::sync::Arc;
 
+mod migrations;
+use migrations::run_migrations;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Set up database connection
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let Ok(database_url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL is not set. Export it as shown in the README.");
+        std::process::exit(1);
+    };
     let pool = PgPool::connect(&database_url).await?;
 
     // Run migrations
-    sqlx::migrate!("examples/migrations/block3")
-        .run(&pool)
-        .await?;
+    if let Err(message) = run_migrations(&pool, &MIGRATOR).await {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
 
     // Initialize the router
     let app = Router::new()
@@ -31,6 +38,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+static MIGRATOR: Migrator = sqlx::migrate!("examples/migrations/block3");
+
 // Handler function for the root path
 async fn hello_world() -> &'static str {
     "Hello, World!"
@@ -64,6 +73,8 @@ struct LatLong {
     longitude: f64,
 }
 
+// Only printed with `{:?}` at this step, so the fields are never read.
+#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 struct WeatherResponse {
     latitude: f64,
@@ -72,6 +83,7 @@ struct WeatherResponse {
     hourly: Hourly,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 struct Hourly {
     time: Vec<String>,
@@ -112,7 +124,7 @@ async fn fetch_lat_long(city: &str) -> Result<LatLong, Box<dyn std::error::Error
     let response = reqwest::get(&url).await?.json::<GeoResponse>().await?;
     response
         .results
-        .get(0)
+        .first()
         .cloned()
         .ok_or_else(|| "No results found".into())
 }
@@ -125,3 +137,26 @@ async fn fetch_weather(lat_long: LatLong) -> Result<WeatherResponse, Box<dyn std
     let response = reqwest::get(&url).await?.json::<WeatherResponse>().await?;
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn the_migrations_create_only_the_cities_table(pool: PgPool) {
+        let columns: Vec<String> = sqlx::query_scalar(
+            "SELECT column_name::text FROM information_schema.columns \
+             WHERE table_name = 'cities' ORDER BY ordinal_position",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let rate_limits: bool = sqlx::query_scalar("SELECT to_regclass('rate_limits') IS NOT NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(columns, ["id", "name", "latitude", "longitude"]);
+        assert!(!rate_limits);
+    }
+}
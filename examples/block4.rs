@@ -6,10 +6,16 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{migrate::Migrator, PgPool};
 use std::sync::Arc;
 
+mod lat_long_cache;
+use lat_long_cache::LatLongCache;
+mod migrations;
+use migrations::run_migrations;
+
 // Custom error type
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 enum ApiError {
     DatabaseError(sqlx::Error),
@@ -48,30 +54,48 @@ struct ErrorResponse {
     error: String,
 }
 
+struct AppState {
+    pool: PgPool,
+    lat_long_cache: LatLongCache<LatLong>,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let Ok(database_url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL is not set. Export it as shown in the README.");
+        std::process::exit(1);
+    };
     let pool = PgPool::connect(&database_url).await?;
+    if let Err(message) = run_migrations(&pool, &MIGRATOR).await {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
+    let lat_long_cache = LatLongCache::from_env();
 
     let app = Router::new()
         .route("/", get(hello_world))
         .route("/weather", get(weather))
-        .with_state(Arc::new(pool));
+        .with_state(Arc::new(AppState {
+            pool,
+            lat_long_cache,
+        }));
 
     println!("Server running on http://0.0.0.0:3000");
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     Ok(axum::serve(listener, app).await?)
 }
 
+static MIGRATOR: Migrator = sqlx::migrate!("examples/migrations/block3");
+
 async fn hello_world() -> &'static str {
     "Hello, World!"
 }
 
 async fn weather(
     Query(params): Query<WeatherQuery>,
-    State(pool): State<Arc<PgPool>>,
+    State(state): State<Arc<AppState>>,
 ) -> Result<Json<WeatherResponse>, ApiError> {
-    let lat_long = get_lat_long(&pool, &params.city).await?;
+    let lat_long = get_lat_long(&state, &params.city).await?;
     let weather = fetch_weather(lat_long).await?;
     Ok(Json(weather))
 }
@@ -106,15 +130,20 @@ struct Hourly {
     temperature_2m: Vec<f64>,
 }
 
-async fn get_lat_long(pool: &PgPool, city: &str) -> Result<LatLong, ApiError> {
+async fn get_lat_long(state: &AppState, city: &str) -> Result<LatLong, ApiError> {
+    if let Some(lat_long) = state.lat_long_cache.get(city) {
+        return Ok(lat_long);
+    }
+
     let result =
         sqlx::query_as::<_, LatLong>("SELECT latitude, longitude FROM cities WHERE name = $1")
             .bind(city)
-            .fetch_optional(pool)
+            .fetch_optional(&state.pool)
             .await
             .map_err(ApiError::DatabaseError)?;
 
     if let Some(lat_long) = result {
+        state.lat_long_cache.put(city, &lat_long);
         return Ok(lat_long);
     }
 
@@ -124,10 +153,11 @@ async fn get_lat_long(pool: &PgPool, city: &str) -> Result<LatLong, ApiError> {
         .bind(city)
         .bind(lat_long.latitude)
         .bind(lat_long.longitude)
-        .execute(pool)
+        .execute(&state.pool)
         .await
         .map_err(ApiError::DatabaseError)?;
 
+    state.lat_long_cache.put(city, &lat_long);
     Ok(lat_long)
 }
 
@@ -143,7 +173,7 @@ async fn fetch_lat_long(city: &str) -> Result<LatLong, ApiError> {
         .await
         .map_err(ApiError::ExternalApiError)?;
 
-    response.results.get(0).cloned().ok_or(ApiError::NotFound)
+    response.results.first().cloned().ok_or(ApiError::NotFound)
 }
 
 async fn fetch_weather(lat_long: LatLong) -> Result<WeatherResponse, ApiError> {
@@ -159,3 +189,29 @@ async fn fetch_weather(lat_long: LatLong) -> Result<WeatherResponse, ApiError> {
         .map_err(ApiError::ExternalApiError)?;
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn a_repeated_lookup_does_not_query_the_database_again(pool: PgPool) {
+        sqlx::query(
+            "INSERT INTO cities (name, latitude, longitude) VALUES ('Berlin', 52.52, 13.41)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let state = AppState {
+            pool,
+            lat_long_cache: LatLongCache::with_capacity(10),
+        };
+        get_lat_long(&state, "Berlin").await.unwrap();
+
+        // Close the pool: a second query would now fail.
+        state.pool.close().await;
+
+        let lat_long = get_lat_long(&state, "Berlin").await.unwrap();
+        assert_eq!(lat_long.latitude, 52.52);
+    }
+}
@@ -1,66 +1,130 @@
 use askama::Template;
 use askama_axum::Response;
 use axum::{
-    async_trait,
-    extract::{FromRequestParts, Query, State},
-    http::{request::Parts, StatusCode},
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
     response::{Html, IntoResponse},
-    routing::get,
-    Router,
+    routing::{get, post},
+    Json, Router,
 };
-use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{migrate::Migrator, PgPool};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use weather::client_ip::client_ip;
+
+mod auth;
+use auth::{authenticator_from_env, User};
+mod cities;
+use cities::{get_lat_long, store_after_forecast, LatLong};
+mod database_url;
+use database_url::database_url;
+mod debug;
+use debug::debug_geocode;
+mod geocoding;
+mod migration_status;
+use migration_status::{migration_status, MigrationStatus};
+mod migrations;
+use migrations::run_migrations;
+mod prime;
+use prime::prime_city;
+mod rate_limit;
+use rate_limit::RateLimiter;
+mod redaction;
+use redaction::Redaction;
+mod state;
+use state::AppState;
+mod stats;
+use stats::StatsQueries;
+#[cfg(test)]
+mod test_support;
 
-struct User;
-
-#[async_trait]
-impl<S> FromRequestParts<S> for User
-where
-    S: Send + Sync,
-{
-    type Rejection = ApiError;
-
-    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
-        let auth_header = parts
-            .headers
-            .get("Authorization")
-            .and_then(|header| header.to_str().ok());
-
-        if let Some(auth_header) = auth_header {
-            if auth_header.starts_with("Basic ") {
-                let credentials = auth_header.trim_start_matches("Basic ");
-                let decoded = general_purpose::STANDARD
-                    .decode(credentials)
-                    .map_err(|_| ApiError::Unauthorized)?;
-                let decoded_str = String::from_utf8(decoded).map_err(|_| ApiError::Unauthorized)?;
-                let parts: Vec<&str> = decoded_str.splitn(2, ':').collect();
-
-                if parts.len() == 2 && parts[0] == "forecast" && parts[1] == "forecast" {
-                    return Ok(User);
-                }
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let database_url = match database_url(|name| std::env::var(name).ok()) {
+        Ok(url) => url,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
+    let redaction =
+        Redaction::from_setting(std::env::var("DATABASE_URL_REDACTION").ok().as_deref());
+    let pool = PgPool::connect(&database_url)
+        .await
+        .map_err(|e| redaction.redact(&e.to_string()))?;
+    let read_only = std::env::var("DB_READONLY").is_ok_and(|value| value == "true");
+    let geocoding_disabled = std::env::var("GEOCODING_DISABLED").is_ok_and(|value| value == "true");
+    let replica =
+        state::connect_replica(std::env::var("REPLICA_DATABASE_URL").ok(), redaction).await?;
+    let authenticator = match authenticator_from_env() {
+        Ok(authenticator) => authenticator,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
+    let rate_limit = std::env::var("RATE_LIMIT_PER_MINUTE")
+        .ok()
+        .and_then(|limit| limit.parse::<i64>().ok())
+        .unwrap_or(60);
+    let rate_limiter = match std::env::var("RATE_LIMIT_BACKEND").as_deref() {
+        Ok("db") if read_only => {
+            eprintln!("RATE_LIMIT_BACKEND=db needs a writable database; unset DB_READONLY");
+            std::process::exit(1);
+        }
+        Ok("db") => RateLimiter::Database,
+        _ => RateLimiter::memory(),
+    };
+    let trusted_proxies =
+        match rate_limit::trusted_proxies(&std::env::var("TRUSTED_PROXIES").unwrap_or_default()) {
+            Ok(trusted_proxies) => trusted_proxies,
+            Err(message) => {
+                eprintln!("{message}");
+                std::process::exit(1);
             }
+        };
+    // Replicas are migrated through the primary.
+    if !read_only {
+        if let Err(message) = run_migrations(&pool, &MIGRATOR).await {
+            eprintln!("{message}");
+            std::process::exit(1);
         }
-
-        Err(ApiError::Unauthorized)
     }
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool = PgPool::connect(&database_url).await?;
 
+    let state = Arc::new(AppState {
+        pool,
+        replica,
+        read_only,
+        geocoding_disabled,
+        rate_limit,
+        rate_limiter,
+        trusted_proxies,
+        stats_queries: StatsQueries::default(),
+        authenticator,
+        redaction,
+    });
     let app = Router::new()
         .route("/", get(index))
         .route("/weather", get(weather))
-        .route("/stats", get(stats))
-        .with_state(Arc::new(pool));
+        .route("/stats", get(stats::stats))
+        .route("/admin/migrations", get(migrations))
+        .route("/debug/geocode", get(debug_geocode))
+        .route("/cache/prime/:city", post(prime_city))
+        .layer(middleware::map_response_with_state(
+            Arc::clone(&state),
+            redact_database_errors,
+        ))
+        .with_state(state);
 
     println!("Server running on http://0.0.0.0:3000");
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    Ok(axum::serve(listener, app).await?)
+    Ok(axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?)
 }
 
 #[derive(Template)]
@@ -82,10 +146,15 @@ struct WeatherTemplate {
 
 async fn weather(
     Query(params): Query<WeatherQuery>,
-    State(pool): State<Arc<PgPool>>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
 ) -> Result<Html<String>, ApiError> {
-    let lat_long = get_lat_long(&pool, &params.city).await?;
-    let weather = fetch_weather(lat_long).await?;
+    let client = client_ip(peer.ip(), &headers, &state.trusted_proxies);
+    state.check_rate_limit(client).await?;
+    let location = get_lat_long(&state, &params.city).await?;
+    let forecast = fetch_weather(location.lat_long.clone());
+    let weather = store_after_forecast(&state, &params.city, &location, forecast).await?;
     let template = WeatherTemplate {
         city: params.city,
         weather,
@@ -94,17 +163,20 @@ async fn weather(
     Ok(Html(html))
 }
 
-#[derive(Template)]
-#[template(path = "stats.html")]
-struct StatsTemplate {
-    cities: Vec<String>,
-}
-
-async fn stats(_: User, State(pool): State<Arc<PgPool>>) -> Result<Html<String>, ApiError> {
-    let cities = get_last_cities(&pool).await?;
-    let template = StatsTemplate { cities };
-    let html = template.render().map_err(|_| ApiError::TemplateError)?;
-    Ok(Html(html))
+/// The migrations this binary was built with. Block 6 has its own directory,
+/// since the schema it adds (request counts, rate limits) is not used before.
+static MIGRATOR: Migrator = sqlx::migrate!("examples/migrations/block6");
+
+/// Reports which migrations the database has applied and which ones this
+/// build knows about but the database hasn't seen yet.
+async fn migrations(
+    _: User,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<MigrationStatus>, ApiError> {
+    migration_status(&state.pool, &MIGRATOR)
+        .await
+        .map(Json)
+        .map_err(ApiError::DatabaseError)
 }
 
 #[derive(Deserialize)]
@@ -112,22 +184,6 @@ struct WeatherQuery {
     city: String,
 }
 
-#[derive(Serialize)]
-struct StatsResponse {
-    cities: Vec<String>,
-}
-
-#[derive(Deserialize, Debug)]
-struct GeoResponse {
-    results: Vec<LatLong>,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow)]
-struct LatLong {
-    latitude: f64,
-    longitude: f64,
-}
-
 #[derive(Deserialize, Serialize, Debug)]
 struct WeatherResponse {
     latitude: f64,
@@ -142,46 +198,6 @@ struct Hourly {
     temperature_2m: Vec<f64>,
 }
 
-async fn get_lat_long(pool: &PgPool, city: &str) -> Result<LatLong, ApiError> {
-    let result =
-        sqlx::query_as::<_, LatLong>("SELECT latitude, longitude FROM cities WHERE name = $1")
-            .bind(city)
-            .fetch_optional(pool)
-            .await
-            .map_err(ApiError::DatabaseError)?;
-
-    if let Some(lat_long) = result {
-        return Ok(lat_long);
-    }
-
-    let lat_long = fetch_lat_long(city).await?;
-
-    sqlx::query("INSERT INTO cities (name, latitude, longitude) VALUES ($1, $2, $3)")
-        .bind(city)
-        .bind(lat_long.latitude)
-        .bind(lat_long.longitude)
-        .execute(pool)
-        .await
-        .map_err(ApiError::DatabaseError)?;
-
-    Ok(lat_long)
-}
-
-async fn fetch_lat_long(city: &str) -> Result<LatLong, ApiError> {
-    let url = format!(
-        "https://geocoding-api.open-meteo.com/v1/search?name={}&count=1&language=en&format=json",
-        city
-    );
-    let response = reqwest::get(&url)
-        .await
-        .map_err(ApiError::ExternalApiError)?
-        .json::<GeoResponse>()
-        .await
-        .map_err(ApiError::ExternalApiError)?;
-
-    response.results.get(0).cloned().ok_or(ApiError::NotFound)
-}
-
 async fn fetch_weather(lat_long: LatLong) -> Result<WeatherResponse, ApiError> {
     let url = format!(
         "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&hourly=temperature_2m",
@@ -196,36 +212,42 @@ async fn fetch_weather(lat_long: LatLong) -> Result<WeatherResponse, ApiError> {
     Ok(response)
 }
 
-async fn get_last_cities(pool: &PgPool) -> Result<Vec<String>, ApiError> {
-    let cities = sqlx::query_scalar("SELECT name FROM cities ORDER BY id DESC LIMIT 10")
-        .fetch_all(pool)
-        .await
-        .map_err(ApiError::DatabaseError)?;
-    Ok(cities)
-}
-
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 enum ApiError {
+    BadRequest(String),
     DatabaseError(sqlx::Error),
     ExternalApiError(reqwest::Error),
     NotFound,
     Unauthorized,
+    TooManyRequests,
     TemplateError,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
-            ApiError::DatabaseError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            ),
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::DatabaseError(e) => {
+                // The details are added by `redact_database_errors`, which
+                // knows how much of them to hide.
+                let mut response =
+                    (StatusCode::INTERNAL_SERVER_ERROR, Html("Database error")).into_response();
+                response
+                    .extensions_mut()
+                    .insert(DatabaseErrorDetail(e.to_string()));
+                return response;
+            }
             ApiError::ExternalApiError(e) => (
                 StatusCode::BAD_GATEWAY,
                 format!("External API error: {}", e),
             ),
             ApiError::NotFound => (StatusCode::NOT_FOUND, "Not found".to_string()),
             ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            ApiError::TooManyRequests => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests".to_string(),
+            ),
             ApiError::TemplateError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Template error".to_string(),
@@ -236,7 +258,193 @@ impl IntoResponse for ApiError {
     }
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+/// The unredacted message of a database error, carried from
+/// [`ApiError::into_response`] to [`redact_database_errors`].
+#[derive(Clone)]
+struct DatabaseErrorDetail(String);
+
+/// Adds a database error's message to its response, redacted as the state
+/// says, so that connection strings in it never reach the client.
+async fn redact_database_errors(
+    State(state): State<Arc<AppState>>,
+    mut response: Response,
+) -> Response {
+    match response.extensions_mut().remove::<DatabaseErrorDetail>() {
+        Some(DatabaseErrorDetail(message)) => (
+            response.status(),
+            Html(format!(
+                "Database error: {}",
+                state.redaction.redact(&message)
+            )),
+        )
+            .into_response(),
+        None => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    /// The body of the response to a database error mentioning a
+    /// connection string, as sent with `redaction`.
+    async fn database_error_body(redaction: Redaction) -> String {
+        let error = sqlx::Error::Configuration(
+            "invalid connection string postgres://forecast:hunter2@db/weather".into(),
+        );
+        let state = AppState {
+            redaction,
+            ..AppState::for_tests(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+        };
+
+        let response = redact_database_errors(
+            State(Arc::new(state)),
+            ApiError::DatabaseError(error).into_response(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn database_error_responses_do_not_reveal_the_password() {
+        let body = database_error_body(Redaction::Password).await;
+
+        assert!(
+            body.contains("postgres://forecast:***@db/weather"),
+            "{body}"
+        );
+        assert!(!body.contains("hunter2"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn database_error_responses_follow_the_configured_redaction() {
+        let body = database_error_body(Redaction::Full).await;
+
+        assert_eq!(
+            body,
+            "Database error: error with configuration: invalid connection string [redacted]"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_database_error_response_has_no_details_before_redaction() {
+        let error = sqlx::Error::Configuration("postgres://forecast:hunter2@db/weather".into());
+
+        let response = ApiError::DatabaseError(error).into_response();
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"Database error");
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn migrations_lists_what_the_database_has_applied(pool: PgPool) {
+        let Json(status) = migrations(User, State(Arc::new(AppState::for_tests(pool))))
+            .await
+            .unwrap();
+
+        let known: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+        let applied: Vec<i64> = status.applied.iter().map(|m| m.version).collect();
+        assert_eq!(applied, known);
+        assert!(status.pending.is_empty());
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn database_limited(pool: PgPool, rate_limit: i64) -> AppState {
+        AppState {
+            rate_limit,
+            rate_limiter: RateLimiter::Database,
+            ..AppState::for_tests(pool)
+        }
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn the_database_rate_limiter_holds_across_instances(pool: PgPool) {
+        let instances = [database_limited(pool.clone(), 3), database_limited(pool, 3)];
+
+        for instance in instances.iter().cycle().take(3) {
+            instance.check_rate_limit(ip("203.0.113.7")).await.unwrap();
+        }
+        let over = instances[1].check_rate_limit(ip("203.0.113.7")).await;
+        let other_client = instances[0].check_rate_limit(ip("198.51.100.1")).await;
+
+        assert!(matches!(over, Err(ApiError::TooManyRequests)));
+        assert!(other_client.is_ok());
+    }
+
+    #[tokio::test]
+    async fn the_memory_rate_limiter_blocks_once_the_limit_is_exceeded() {
+        let state = AppState {
+            rate_limit: 2,
+            ..AppState::for_tests(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+        };
+
+        state.check_rate_limit(ip("203.0.113.7")).await.unwrap();
+        state.check_rate_limit(ip("203.0.113.7")).await.unwrap();
+        let over = state.check_rate_limit(ip("203.0.113.7")).await;
+
+        assert!(matches!(over, Err(ApiError::TooManyRequests)));
+    }
+
+    /// State allowing one `/weather` request a minute per client, with
+    /// proxies in `10.0.0.0/8` trusted, where `client` has used it up.
+    async fn limited_after_one_request(client: &str) -> Arc<AppState> {
+        let state = AppState {
+            rate_limit: 1,
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..AppState::for_tests(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+        };
+        state.check_rate_limit(ip(client)).await.unwrap();
+        Arc::new(state)
+    }
+
+    /// Requests Berlin's weather from `peer`, forwarded for `forwarded_for`.
+    async fn weather_from(
+        state: &Arc<AppState>,
+        peer: &str,
+        forwarded_for: &str,
+    ) -> Result<Html<String>, ApiError> {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", forwarded_for.parse().unwrap());
+        weather(
+            Query(WeatherQuery {
+                city: "Berlin".to_string(),
+            }),
+            State(Arc::clone(state)),
+            ConnectInfo(SocketAddr::new(ip(peer), 40_000)),
+            headers,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn clients_behind_a_trusted_proxy_are_limited_by_their_own_address() {
+        let state = limited_after_one_request("203.0.113.7").await;
+
+        let over = weather_from(&state, "10.0.0.2", "203.0.113.7").await;
+
+        assert!(matches!(over, Err(ApiError::TooManyRequests)));
+        // The proxy's own address was never counted.
+        assert!(state.check_rate_limit(ip("10.0.0.2")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn an_untrusted_peer_cannot_dodge_the_limit_with_a_forwarded_header() {
+        let state = limited_after_one_request("203.0.113.7").await;
+
+        let over = weather_from(&state, "203.0.113.7", "198.51.100.1").await;
+
+        assert!(matches!(over, Err(ApiError::TooManyRequests)));
+        assert!(state.check_rate_limit(ip("198.51.100.1")).await.is_ok());
+    }
 }
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::geocoding::fetch_lat_long;
+use crate::{ApiError, AppState, WeatherResponse};
+
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct LatLong {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Coordinates for a city, and whether they came from the database.
+pub struct CityLocation {
+    pub lat_long: LatLong,
+    pub stored: bool,
+}
+
+/// Looks the city up in the database, geocoding it on a miss. Newly geocoded
+/// cities are not stored; see [`store_city`].
+pub async fn get_lat_long(state: &AppState, city: &str) -> Result<CityLocation, ApiError> {
+    let result =
+        sqlx::query_as::<_, LatLong>("SELECT latitude, longitude FROM cities WHERE name = $1")
+            .bind(city)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+
+    if let Some(lat_long) = result {
+        if !state.read_only {
+            sqlx::query(
+                "UPDATE cities SET request_count = request_count + 1, last_requested_at = now() \
+                 WHERE name = $1",
+            )
+            .bind(city)
+            .execute(&state.pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+            record_request(&state.pool, city).await?;
+        }
+        return Ok(CityLocation {
+            lat_long,
+            stored: true,
+        });
+    }
+
+    if state.geocoding_disabled {
+        return Err(ApiError::NotFound);
+    }
+    let lat_long = fetch_lat_long(city).await?;
+    Ok(CityLocation {
+        lat_long,
+        stored: false,
+    })
+}
+
+pub async fn store_city(state: &AppState, city: &str, lat_long: &LatLong) -> Result<(), ApiError> {
+    if state.read_only {
+        println!("Database is read-only, not persisting {city}");
+        return Ok(());
+    }
+
+    // Another request may have stored the city while we fetched the forecast.
+    sqlx::query(
+        "INSERT INTO cities (name, latitude, longitude) VALUES ($1, $2, $3) \
+         ON CONFLICT (name) DO NOTHING",
+    )
+    .bind(city)
+    .bind(lat_long.latitude)
+    .bind(lat_long.longitude)
+    .execute(&state.pool)
+    .await
+    .map_err(ApiError::DatabaseError)?;
+    record_request(&state.pool, city).await
+}
+
+/// Waits for `forecast` and, only if it arrived, stores a newly geocoded
+/// city, so that cities we couldn't serve a forecast for aren't remembered.
+pub async fn store_after_forecast(
+    state: &AppState,
+    city: &str,
+    location: &CityLocation,
+    forecast: impl std::future::Future<Output = Result<WeatherResponse, ApiError>>,
+) -> Result<WeatherResponse, ApiError> {
+    let weather = forecast.await?;
+    if !location.stored {
+        store_city(state, city, &location.lat_long).await?;
+    }
+    Ok(weather)
+}
+
+/// Adds a request for the stored `city` to the `requests` history.
+async fn record_request(pool: &PgPool, city: &str) -> Result<(), ApiError> {
+    sqlx::query("INSERT INTO requests (city_id) SELECT id FROM cities WHERE name = $1")
+        .bind(city)
+        .execute(pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{berlin, request_count, seed_cities, unreachable_pool};
+    use crate::Hourly;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn geocoded_cities_are_stored(pool: PgPool) {
+        let state = AppState::for_tests(pool);
+
+        store_city(&state, "Berlin", &berlin()).await.unwrap();
+
+        assert_eq!(request_count(&state.pool, "Berlin").await, Some(1));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn read_only_mode_does_not_store_geocoded_cities(pool: PgPool) {
+        let state = AppState {
+            read_only: true,
+            ..AppState::for_tests(pool)
+        };
+
+        store_city(&state, "Berlin", &berlin()).await.unwrap();
+
+        assert_eq!(request_count(&state.pool, "Berlin").await, None);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn read_only_mode_serves_stored_cities_without_counting_them(pool: PgPool) {
+        store_city(&AppState::for_tests(pool.clone()), "Berlin", &berlin())
+            .await
+            .unwrap();
+        let state = AppState {
+            read_only: true,
+            ..AppState::for_tests(pool)
+        };
+
+        let location = get_lat_long(&state, "Berlin").await.unwrap();
+
+        assert!(location.stored);
+        assert_eq!(location.lat_long.latitude, 52.52);
+        assert_eq!(request_count(&state.pool, "Berlin").await, Some(1));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn requests_for_stored_cities_are_added_to_the_history(pool: PgPool) {
+        seed_cities(&pool).await;
+        let state = AppState::for_tests(pool);
+
+        get_lat_long(&state, "Berlin").await.unwrap();
+        get_lat_long(&state, "Berlin").await.unwrap();
+
+        let history: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM requests JOIN cities ON cities.id = requests.city_id \
+             WHERE requested_at > now() - interval '1 minute'",
+        )
+        .fetch_all(&state.pool)
+        .await
+        .unwrap();
+        assert_eq!(history, ["Berlin", "Berlin"]);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn writes_go_to_the_primary_even_with_a_replica(pool: PgPool) {
+        let state = AppState {
+            replica: Some(unreachable_pool()),
+            ..AppState::for_tests(pool)
+        };
+
+        store_city(&state, "Berlin", &berlin()).await.unwrap();
+        get_lat_long(&state, "Berlin").await.unwrap();
+
+        assert_eq!(request_count(&state.pool, "Berlin").await, Some(2));
+    }
+
+    fn berlin_forecast() -> WeatherResponse {
+        WeatherResponse {
+            latitude: 52.52,
+            longitude: 13.41,
+            timezone: "GMT".to_string(),
+            hourly: Hourly {
+                time: vec!["2024-05-01T12:00".to_string()],
+                temperature_2m: vec![18.4],
+            },
+        }
+    }
+
+    fn geocoded_berlin() -> CityLocation {
+        CityLocation {
+            lat_long: berlin(),
+            stored: false,
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn a_city_is_stored_once_its_forecast_arrives(pool: PgPool) {
+        let state = AppState::for_tests(pool);
+
+        store_after_forecast(&state, "Berlin", &geocoded_berlin(), async {
+            Ok(berlin_forecast())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(request_count(&state.pool, "Berlin").await, Some(1));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn a_city_whose_forecast_fails_is_not_stored(pool: PgPool) {
+        let state = AppState::for_tests(pool);
+
+        let result = store_after_forecast(&state, "Berlin", &geocoded_berlin(), async {
+            Err(ApiError::NotFound)
+        })
+        .await;
+
+        assert!(matches!(result, Err(ApiError::NotFound)));
+        assert_eq!(request_count(&state.pool, "Berlin").await, None);
+    }
+}
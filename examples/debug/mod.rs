@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use crate::geocoding::geocoding_url;
+use crate::{ApiError, AppState, User, WeatherQuery};
+
+/// Returns the geocoding API's response as is, with all candidates and
+/// fields, to help work out why a city resolves somewhere unexpected.
+/// Deliberately bypasses the `cities` table.
+pub async fn debug_geocode(
+    _: User,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<WeatherQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if state.geocoding_disabled {
+        return Err(ApiError::NotFound);
+    }
+    let response = fetch_raw_json(&geocoding_url(&params.city, 10)).await?;
+    Ok(Json(response))
+}
+
+/// The JSON body at `url`, whatever its shape.
+async fn fetch_raw_json(url: &str) -> Result<serde_json::Value, ApiError> {
+    reqwest::get(url)
+        .await
+        .map_err(ApiError::ExternalApiError)?
+        .json::<serde_json::Value>()
+        .await
+        .map_err(ApiError::ExternalApiError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use sqlx::PgPool;
+
+    /// Serves `body` as JSON on a local port and returns the URL to fetch it.
+    async fn serve_json(body: serde_json::Value) -> String {
+        let app = Router::new().route("/v1/search", get(move || async move { Json(body) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{address}/v1/search?name=Springfield")
+    }
+
+    #[tokio::test]
+    async fn debug_geocode_returns_the_raw_response_with_every_candidate_and_field() {
+        let raw = serde_json::json!({
+            "results": [
+                {
+                    "id": 4951788,
+                    "name": "Springfield",
+                    "latitude": 42.10148,
+                    "longitude": -72.58981,
+                    "feature_code": "PPLA2",
+                    "country_code": "US",
+                    "admin1": "Massachusetts",
+                    "population": 153606,
+                    "timezone": "America/New_York",
+                },
+                {
+                    "id": 4250542,
+                    "name": "Springfield",
+                    "latitude": 39.80172,
+                    "longitude": -89.64371,
+                    "feature_code": "PPLA",
+                    "country_code": "US",
+                    "admin1": "Illinois",
+                    "timezone": "America/Chicago",
+                },
+            ],
+            "generationtime_ms": 0.81,
+        });
+        let url = serve_json(raw.clone()).await;
+
+        let response = fetch_raw_json(&url).await.unwrap();
+
+        assert_eq!(response, raw);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn debug_geocode_is_not_found_when_geocoding_is_disabled(pool: PgPool) {
+        let state = AppState {
+            geocoding_disabled: true,
+            ..AppState::for_tests(pool)
+        };
+        let query = WeatherQuery {
+            city: "Berlin".to_string(),
+        };
+
+        let result = debug_geocode(User, State(Arc::new(state)), Query(query)).await;
+
+        assert!(matches!(result, Err(ApiError::NotFound)));
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Deserialize;
+
+use crate::{ApiError, LatLong};
+
+#[derive(Deserialize, Debug)]
+struct GeoResponse {
+    results: Vec<LatLong>,
+}
+
+/// The city is percent-encoded, so it can't add parameters to the request.
+pub fn geocoding_url(city: &str, count: u32) -> String {
+    reqwest::Url::parse_with_params(
+        "https://geocoding-api.open-meteo.com/v1/search",
+        &[
+            ("name", city),
+            ("count", &count.to_string()),
+            ("language", "en"),
+            ("format", "json"),
+        ],
+    )
+    .expect("the geocoding endpoint is a valid URL")
+    .into()
+}
+
+pub async fn fetch_lat_long(city: &str) -> Result<LatLong, ApiError> {
+    let url = geocoding_url(city, 1);
+    let response = reqwest::get(&url)
+        .await
+        .map_err(ApiError::ExternalApiError)?
+        .json::<GeoResponse>()
+        .await
+        .map_err(ApiError::ExternalApiError)?;
+
+    response.results.first().cloned().ok_or(ApiError::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{request_count, seed_cities};
+    use crate::{get_lat_long, AppState};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn seeded_cities_resolve_with_geocoding_disabled(pool: PgPool) {
+        seed_cities(&pool).await;
+        let state = AppState {
+            geocoding_disabled: true,
+            ..AppState::for_tests(pool)
+        };
+
+        let location = get_lat_long(&state, "Paris").await.unwrap();
+
+        assert!(location.stored);
+        assert_eq!(location.lat_long.latitude, 48.85);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn unseeded_cities_are_not_found_with_geocoding_disabled(pool: PgPool) {
+        let state = AppState {
+            geocoding_disabled: true,
+            ..AppState::for_tests(pool)
+        };
+
+        // Geocoding London would either find it or, offline, fail to reach
+        // the API; a 404 means it was never attempted.
+        let result = get_lat_long(&state, "London").await;
+
+        assert!(matches!(result, Err(ApiError::NotFound)));
+        assert_eq!(request_count(&state.pool, "London").await, None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+// Opt-in in-memory cache in front of the database, enabled by setting
+// `LAT_LONG_CACHE_SIZE` to the number of cities to keep. Shared by the
+// blocks that look cities up in Postgres.
+pub struct LatLongCache<V>(Option<Mutex<LruCache<V>>>);
+
+impl<V: Clone> LatLongCache<V> {
+    pub fn from_env() -> Self {
+        let size = std::env::var("LAT_LONG_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .filter(|&size| size > 0);
+        LatLongCache(size.map(|size| Mutex::new(LruCache::new(size))))
+    }
+
+    #[cfg(test)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        LatLongCache(Some(Mutex::new(LruCache::new(capacity))))
+    }
+
+    pub fn get(&self, city: &str) -> Option<V> {
+        self.0.as_ref()?.lock().unwrap().get(city)
+    }
+
+    pub fn put(&self, city: &str, lat_long: &V) {
+        if let Some(cache) = &self.0 {
+            cache.lock().unwrap().put(city, lat_long.clone());
+        }
+    }
+}
+
+// A small least-recently-used cache. Every use stamps a city with the next
+// tick; `order` maps ticks back to cities, so the least recently used city
+// is always its first entry.
+struct LruCache<V> {
+    capacity: usize,
+    entries: HashMap<String, (V, u64)>,
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl<V: Clone> LruCache<V> {
+    fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, city: &str) -> Option<V> {
+        let tick = self.next_tick();
+        let (lat_long, used) = self.entries.get_mut(city)?;
+        let city = self.order.remove(&*used).expect("every entry has a tick");
+        *used = tick;
+        self.order.insert(tick, city);
+        Some(lat_long.clone())
+    }
+
+    fn put(&mut self, city: &str, lat_long: V) {
+        let tick = self.next_tick();
+        if let Some((_, used)) = self.entries.insert(city.to_string(), (lat_long, tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(tick, city.to_string());
+        if self.entries.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_cache_evicts_the_least_recently_used_city() {
+        let mut cache = LruCache::new(2);
+        cache.put("Berlin", 52.52);
+        cache.put("Paris", 48.85);
+        cache.get("Berlin");
+
+        cache.put("Rome", 41.89);
+
+        assert_eq!(cache.get("Berlin"), Some(52.52));
+        assert_eq!(cache.get("Paris"), None);
+        assert_eq!(cache.get("Rome"), Some(41.89));
+    }
+
+    #[test]
+    fn lru_cache_counts_a_replaced_city_as_used() {
+        let mut cache = LruCache::new(2);
+        cache.put("Berlin", 52.52);
+        cache.put("Paris", 48.85);
+        cache.put("Berlin", 52.53);
+
+        cache.put("Rome", 41.89);
+
+        assert_eq!(cache.get("Berlin"), Some(52.53));
+        assert_eq!(cache.get("Paris"), None);
+        assert_eq!(cache.order.len(), 2);
+    }
+
+    #[test]
+    fn lru_cache_with_capacity_one_keeps_only_the_latest_city() {
+        let mut cache = LruCache::new(1);
+        cache.put("Berlin", 52.52);
+
+        cache.put("Paris", 48.85);
+
+        assert_eq!(cache.get("Berlin"), None);
+        assert_eq!(cache.get("Paris"), Some(48.85));
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn lru_cache_keeps_evicting_in_order_of_use() {
+        let mut cache = LruCache::new(2);
+        cache.put("Berlin", 52.52);
+        cache.put("Paris", 48.85);
+
+        cache.put("Rome", 41.89);
+        cache.get("Paris");
+        cache.put("Madrid", 40.42);
+        cache.put("Vienna", 48.21);
+
+        assert_eq!(cache.get("Berlin"), None);
+        assert_eq!(cache.get("Rome"), None);
+        assert_eq!(cache.get("Paris"), None);
+        assert_eq!(cache.get("Madrid"), Some(40.42));
+        assert_eq!(cache.get("Vienna"), Some(48.21));
+        assert_eq!((cache.entries.len(), cache.order.len()), (2, 2));
+    }
+
+    #[test]
+    fn lru_cache_miss_does_not_change_the_order() {
+        let mut cache = LruCache::new(2);
+        cache.put("Berlin", 52.52);
+        cache.put("Paris", 48.85);
+
+        assert_eq!(cache.get("Rome"), None);
+        cache.put("Rome", 41.89);
+
+        assert_eq!(cache.get("Berlin"), None);
+        assert_eq!(cache.get("Paris"), Some(48.85));
+        assert_eq!(cache.get("Rome"), Some(41.89));
+    }
+
+    #[test]
+    fn a_cache_with_capacity_evicts_through_the_shared_handle() {
+        let cache = LatLongCache::with_capacity(2);
+        cache.put("Berlin", &52.52);
+        cache.put("Paris", &48.85);
+        cache.get("Berlin");
+
+        cache.put("Rome", &41.89);
+
+        assert_eq!(cache.get("Berlin"), Some(52.52));
+        assert_eq!(cache.get("Paris"), None);
+        assert_eq!(cache.get("Rome"), Some(41.89));
+    }
+
+    #[test]
+    fn a_disabled_cache_remembers_nothing() {
+        let cache = LatLongCache(None);
+
+        cache.put("Berlin", &52.52);
+
+        assert_eq!(cache.get("Berlin"), None);
+    }
+}
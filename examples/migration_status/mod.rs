@@ -0,0 +1,101 @@
+use serde::Serialize;
+use sqlx::{migrate::Migrator, PgPool};
+
+// What `GET /admin/migrations` reports: the migrations the database has
+// applied, and the ones this build knows about but the database hasn't seen
+// yet.
+#[derive(Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<MigrationInfo>,
+    pub pending: Vec<MigrationInfo>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub description: String,
+}
+
+pub async fn migration_status(
+    pool: &PgPool,
+    migrator: &Migrator,
+) -> Result<MigrationStatus, sqlx::Error> {
+    // `_sqlx_migrations` only exists once a migration has been run.
+    let table_exists: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(pool)
+            .await?;
+    let applied = if table_exists {
+        sqlx::query_as::<_, MigrationInfo>(
+            "SELECT version, description FROM _sqlx_migrations WHERE success ORDER BY version",
+        )
+        .fetch_all(pool)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    let pending = migrator
+        .iter()
+        .filter(|migration| !applied.iter().any(|m| m.version == migration.version))
+        .map(|migration| MigrationInfo {
+            version: migration.version,
+            description: migration.description.to_string(),
+        })
+        .collect();
+    Ok(MigrationStatus { applied, pending })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MIGRATOR: Migrator = sqlx::migrate!("examples/migrations/block6");
+
+    fn versions(migrations: &[MigrationInfo]) -> Vec<i64> {
+        migrations.iter().map(|m| m.version).collect()
+    }
+
+    fn known() -> Vec<i64> {
+        MIGRATOR.iter().map(|m| m.version).collect()
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn lists_what_the_database_has_applied(pool: PgPool) {
+        let status = migration_status(&pool, &MIGRATOR).await.unwrap();
+
+        assert_eq!(versions(&status.applied), known());
+        assert!(status.pending.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn lists_everything_as_pending_on_a_fresh_database(pool: PgPool) {
+        let status = migration_status(&pool, &MIGRATOR).await.unwrap();
+
+        assert!(status.applied.is_empty());
+        assert_eq!(versions(&status.pending), known());
+        assert!(!status.pending[0].description.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn lists_only_the_migrations_still_to_run(pool: PgPool) {
+        let first = MIGRATOR.iter().next().unwrap();
+        sqlx::query(
+            "CREATE TABLE _sqlx_migrations (version BIGINT PRIMARY KEY, description TEXT NOT NULL, success BOOLEAN NOT NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO _sqlx_migrations VALUES ($1, $2, true)")
+            .bind(first.version)
+            .bind(first.description.as_ref())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let status = migration_status(&pool, &MIGRATOR).await.unwrap();
+
+        assert_eq!(versions(&status.applied), [first.version]);
+        assert_eq!(versions(&status.pending), known()[1..]);
+    }
+}
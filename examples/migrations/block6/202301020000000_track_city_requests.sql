@@ -0,0 +1,3 @@
+ALTER TABLE cities
+    ADD COLUMN IF NOT EXISTS request_count BIGINT NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS last_requested_at TIMESTAMPTZ NOT NULL DEFAULT now();
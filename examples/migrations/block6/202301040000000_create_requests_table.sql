@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS requests (
+    id BIGSERIAL PRIMARY KEY,
+    city_id INTEGER NOT NULL REFERENCES cities (id) ON DELETE CASCADE,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS requests_requested_at ON requests (requested_at);
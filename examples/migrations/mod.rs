@@ -0,0 +1,90 @@
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    PgPool,
+};
+
+/// Applies the pending migrations. On failure, the message names the
+/// migration that failed, when sqlx says which one, and the reason.
+pub async fn run_migrations(pool: &PgPool, migrator: &Migrator) -> Result<(), String> {
+    let Err(e) = migrator.run(pool).await else {
+        return Ok(());
+    };
+    let version = match &e {
+        MigrateError::ExecuteMigration(_, version)
+        | MigrateError::VersionMismatch(version)
+        | MigrateError::VersionMissing(version)
+        | MigrateError::Dirty(version) => Some(*version),
+        _ => None,
+    };
+    Err(match version {
+        Some(version) => match migrator.iter().find(|m| m.version == version) {
+            Some(migration) => format!(
+                "Migration {version} ({}) failed: {e}",
+                migration.description
+            ),
+            // An applied migration whose file is gone.
+            None => format!("Migration {version} failed: {e}"),
+        },
+        None => format!("Running migrations failed: {e}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A fresh migrations directory holding `files`.
+    fn migrations_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("migrations-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, sql) in files {
+            std::fs::write(dir.join(file), sql).unwrap();
+        }
+        dir
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn a_broken_migration_is_named_in_the_error(pool: PgPool) {
+        let dir = migrations_dir(
+            "broken",
+            &[
+                ("1_create_cities.sql", "CREATE TABLE cities (name TEXT);"),
+                ("2_add_country.sql", "ALTER TABLEE cities ADD country TEXT;"),
+            ],
+        );
+        let migrator = Migrator::new(dir.as_path()).await.unwrap();
+
+        let message = run_migrations(&pool, &migrator).await.unwrap_err();
+
+        assert!(
+            message.starts_with("Migration 2 (add country) failed:"),
+            "{message}"
+        );
+        assert!(message.contains("syntax error"), "{message}");
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn an_edited_migration_is_named_in_the_error(pool: PgPool) {
+        let dir = migrations_dir(
+            "edited",
+            &[("1_create_cities.sql", "CREATE TABLE cities (name TEXT);")],
+        );
+        let migrator = Migrator::new(dir.as_path()).await.unwrap();
+        run_migrations(&pool, &migrator).await.unwrap();
+        std::fs::write(
+            dir.join("1_create_cities.sql"),
+            "CREATE TABLE cities (name VARCHAR);",
+        )
+        .unwrap();
+        let migrator = Migrator::new(dir.as_path()).await.unwrap();
+
+        let message = run_migrations(&pool, &migrator).await.unwrap_err();
+
+        assert!(
+            message.starts_with("Migration 1 (create cities) failed:"),
+            "{message}"
+        );
+    }
+}
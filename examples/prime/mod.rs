@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Serialize;
+
+use crate::cities::{get_lat_long, store_city};
+use crate::{ApiError, AppState, User};
+
+#[derive(Serialize)]
+pub struct PrimedCity {
+    city: String,
+    latitude: f64,
+    longitude: f64,
+    /// Whether the city was already in the database.
+    already_cached: bool,
+}
+
+/// Resolves one city and stores it, so operators can check that a specific
+/// city resolves (and where to) before users ask for it.
+pub async fn prime_city(
+    _: User,
+    State(state): State<Arc<AppState>>,
+    Path(city): Path<String>,
+) -> Result<Json<PrimedCity>, ApiError> {
+    let location = get_lat_long(&state, &city).await?;
+    if !location.stored {
+        store_city(&state, &city, &location.lat_long).await?;
+    }
+    Ok(Json(PrimedCity {
+        latitude: location.lat_long.latitude,
+        longitude: location.lat_long.longitude,
+        already_cached: location.stored,
+        city,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{request_count, seed_cities};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn priming_a_stored_city_returns_its_coordinates(pool: PgPool) {
+        seed_cities(&pool).await;
+        let state = AppState {
+            geocoding_disabled: true,
+            ..AppState::for_tests(pool)
+        };
+
+        let Json(primed) = prime_city(User, State(Arc::new(state)), Path("Paris".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(primed.city, "Paris");
+        assert_eq!((primed.latitude, primed.longitude), (48.85, 2.35));
+        assert!(primed.already_cached);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn priming_a_city_that_does_not_resolve_is_not_found(pool: PgPool) {
+        let state = Arc::new(AppState {
+            geocoding_disabled: true,
+            ..AppState::for_tests(pool)
+        });
+
+        let result = prime_city(User, State(state.clone()), Path("Atlantis".to_string())).await;
+
+        assert!(matches!(result, Err(ApiError::NotFound)));
+        assert_eq!(request_count(&state.pool, "Atlantis").await, None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sqlx::PgPool;
+use weather::client_ip::Cidr;
+
+const RATE_LIMIT_WINDOW_SECS: i64 = 60;
+
+// Clients the in-memory rate limiter tracks before pruning old windows.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+// Where request counts are kept, chosen with `RATE_LIMIT_BACKEND`.
+pub enum RateLimiter {
+    // Per instance: each instance allows the full limit.
+    Memory(Mutex<HashMap<String, (i64, i64)>>),
+    // In the `rate_limits` table, so the limit holds across all instances.
+    Database,
+}
+
+impl RateLimiter {
+    pub fn memory() -> Self {
+        RateLimiter::Memory(Mutex::new(HashMap::new()))
+    }
+
+    /// Counts a request from `client` in the current window and returns how
+    /// many it has made in that window so far.
+    pub async fn count(&self, pool: &PgPool, client: IpAddr) -> Result<i64, sqlx::Error> {
+        let window = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64
+            / RATE_LIMIT_WINDOW_SECS;
+        let client = client.to_string();
+        match self {
+            RateLimiter::Memory(counts) => {
+                let mut counts = counts.lock().unwrap();
+                // Forget clients from earlier windows before the map grows large.
+                if counts.len() >= MAX_TRACKED_CLIENTS {
+                    counts.retain(|_, (w, _)| *w == window);
+                }
+                let (w, count) = counts.entry(client).or_insert((window, 0));
+                if *w != window {
+                    *w = window;
+                    *count = 0;
+                }
+                *count += 1;
+                Ok(*count)
+            }
+            RateLimiter::Database => count_in_database(pool, &client, window).await,
+        }
+    }
+}
+
+/// Adds a request to the client's count for `window` and returns the new count.
+async fn count_in_database(pool: &PgPool, client: &str, window: i64) -> Result<i64, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(
+        "INSERT INTO rate_limits (client, window_start, count) VALUES ($1, $2, 1) \
+         ON CONFLICT (client, window_start) DO UPDATE SET count = rate_limits.count + 1 \
+         RETURNING count",
+    )
+    .bind(client)
+    .bind(window)
+    .fetch_one(pool)
+    .await?;
+    // The client's first request in a window clears its earlier ones.
+    if count == 1 {
+        sqlx::query("DELETE FROM rate_limits WHERE client = $1 AND window_start < $2")
+            .bind(client)
+            .bind(window)
+            .execute(pool)
+            .await?;
+    }
+    Ok(count)
+}
+
+/// Parses `TRUSTED_PROXIES`, a comma-separated list of CIDRs such as
+/// `10.0.0.0/8,fd00::/8`.
+pub fn trusted_proxies(list: &str) -> Result<Vec<Cidr>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|cidr| !cidr.is_empty())
+        .map(|cidr| cidr.parse().map_err(|e| format!("TRUSTED_PROXIES: {e}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn trusted_proxies_are_parsed_from_a_comma_separated_list() {
+        let trusted = trusted_proxies(" 10.0.0.0/8, fd00::/8 ,").unwrap();
+
+        assert_eq!(trusted.len(), 2);
+        assert!(trusted[1].contains(ip("fd00::1")));
+        assert!(trusted_proxies("").unwrap().is_empty());
+        assert!(trusted_proxies("10.0.0.0/33").is_err());
+    }
+
+    #[tokio::test]
+    async fn the_memory_limiter_counts_each_client_separately() {
+        let limiter = RateLimiter::memory();
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+
+        for expected in 1..=3 {
+            let count = limiter.count(&pool, ip("203.0.113.7")).await.unwrap();
+            assert_eq!(count, expected);
+        }
+        let other = limiter.count(&pool, ip("198.51.100.1")).await.unwrap();
+
+        assert_eq!(other, 1);
+    }
+
+    #[sqlx::test(migrations = "examples/migrations/block6")]
+    async fn database_counts_restart_in_each_window_and_drop_older_ones(pool: PgPool) {
+        for expected in 1..=2 {
+            let count = count_in_database(&pool, "203.0.113.7", 100).await.unwrap();
+            assert_eq!(count, expected);
+        }
+
+        let next_window = count_in_database(&pool, "203.0.113.7", 101).await.unwrap();
+
+        assert_eq!(next_window, 1);
+        let windows: Vec<i64> =
+            sqlx::query_scalar("SELECT window_start FROM rate_limits WHERE client = $1")
+                .bind("203.0.113.7")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(windows, [101]);
+    }
+}
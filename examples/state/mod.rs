@@ -0,0 +1,126 @@
+use std::net::IpAddr;
+
+use sqlx::PgPool;
+use weather::client_ip::Cidr;
+
+use crate::auth::Authenticator;
+use crate::rate_limit::RateLimiter;
+use crate::redaction::Redaction;
+use crate::stats::StatsQueries;
+use crate::ApiError;
+
+pub struct AppState {
+    pub pool: PgPool,
+    // Optional read replica (`REPLICA_DATABASE_URL`) for read-only endpoints.
+    pub replica: Option<PgPool>,
+    // Set `DB_READONLY=true` when running against a read-only replica. Cities
+    // are still geocoded, but never written back to the database.
+    pub read_only: bool,
+    // Set `GEOCODING_DISABLED=true` when every city is seeded in the database.
+    // Unknown cities are then a 404 instead of a call to the geocoding API.
+    pub geocoding_disabled: bool,
+    // `/weather` requests each client may make per minute (`RATE_LIMIT_PER_MINUTE`).
+    pub rate_limit: i64,
+    pub rate_limiter: RateLimiter,
+    // Reverse proxies (`TRUSTED_PROXIES`) whose forwarding headers name the
+    // client a request is counted against.
+    pub trusted_proxies: Vec<Cidr>,
+    pub stats_queries: StatsQueries,
+    pub authenticator: Box<dyn Authenticator>,
+    // How database errors in responses hide connection strings.
+    pub redaction: Redaction,
+}
+
+impl AppState {
+    /// The pool for queries that only read, preferring the replica.
+    pub fn read_pool(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+
+    /// Counts a request from `client` and fails once it is over the limit for
+    /// the current minute.
+    pub async fn check_rate_limit(&self, client: IpAddr) -> Result<(), ApiError> {
+        let count = self
+            .rate_limiter
+            .count(&self.pool, client)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+        if count > self.rate_limit {
+            return Err(ApiError::TooManyRequests);
+        }
+        Ok(())
+    }
+}
+
+/// Connects to the read replica at `REPLICA_DATABASE_URL`, if one is set.
+pub async fn connect_replica(
+    url: Option<String>,
+    redaction: Redaction,
+) -> Result<Option<PgPool>, String> {
+    match url {
+        Some(url) => PgPool::connect(&url)
+            .await
+            .map(Some)
+            .map_err(|e| redaction.redact(&e.to_string())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State with every option at its default, on `pool`.
+    pub fn for_tests(pool: PgPool) -> Self {
+        AppState {
+            pool,
+            replica: None,
+            read_only: false,
+            geocoding_disabled: false,
+            rate_limit: 60,
+            rate_limiter: RateLimiter::memory(),
+            trusted_proxies: Vec::new(),
+            stats_queries: StatsQueries::default(),
+            authenticator: Box::new(crate::auth::BasicAuth),
+            redaction: Redaction::Password,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unreachable_pool;
+
+    async fn database_name(pool: &PgPool) -> Result<String, sqlx::Error> {
+        sqlx::query_scalar("SELECT current_database()::text")
+            .fetch_one(pool)
+            .await
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn reads_go_to_the_replica_when_one_is_configured(pool: PgPool) {
+        let state = AppState {
+            replica: Some(pool.clone()),
+            ..AppState::for_tests(unreachable_pool())
+        };
+
+        let read = database_name(state.read_pool()).await.unwrap();
+
+        assert_eq!(read, database_name(&pool).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn reads_go_to_the_primary_without_a_replica(pool: PgPool) {
+        let state = AppState::for_tests(pool.clone());
+
+        let read = database_name(state.read_pool()).await.unwrap();
+
+        assert_eq!(read, database_name(&pool).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn no_replica_is_connected_without_a_url() {
+        let replica = connect_replica(None, Redaction::Password).await.unwrap();
+
+        assert!(replica.is_none());
+    }
+}
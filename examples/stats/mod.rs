@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+use sqlx::PgPool;
+
+use crate::{ApiError, AppState, User};
+
+#[derive(Template)]
+#[template(path = "stats.html")]
+struct StatsTemplate {
+    cities: Vec<String>,
+}
+
+/// Most cities `/stats` lists, whatever `limit` asks for.
+const MAX_STATS_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct StatsQuery {
+    #[serde(default = "default_stats_limit")]
+    limit: i64,
+    #[serde(default)]
+    order: StatsOrder,
+    /// Only cities requested after this RFC 3339 timestamp, going by the
+    /// `requests` history, for clients polling for new activity.
+    #[serde(default, deserialize_with = "rfc3339")]
+    since: Option<DateTime<Utc>>,
+}
+
+fn default_stats_limit() -> i64 {
+    10
+}
+
+fn rfc3339<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    let since = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&since)
+        .map(|since| Some(since.to_utc()))
+        .map_err(|_| {
+            serde::de::Error::custom(
+                "since must be an RFC 3339 timestamp such as 2024-05-01T12:00:00Z",
+            )
+        })
+}
+
+/// How `/stats` ranks the cities it lists.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+enum StatsOrder {
+    /// Most recently added first.
+    #[default]
+    Inserted,
+    /// Most requested first.
+    Frequency,
+    /// Most recently requested first.
+    Recent,
+}
+
+impl StatsOrder {
+    fn order_by(self) -> &'static str {
+        match self {
+            StatsOrder::Inserted => "id DESC",
+            StatsOrder::Frequency => "request_count DESC, id DESC",
+            StatsOrder::Recent => "last_requested_at DESC, id DESC",
+        }
+    }
+}
+
+pub async fn stats(
+    _: User,
+    Query(params): Query<StatsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, ApiError> {
+    if !(1..=MAX_STATS_LIMIT).contains(&params.limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {MAX_STATS_LIMIT}"
+        )));
+    }
+    let cities = state.stats_queries.run(state.read_pool(), &params).await?;
+    let template = StatsTemplate { cities };
+    let html = template.render().map_err(|_| ApiError::TemplateError)?;
+    Ok(Html(html))
+}
+
+type StatsQueryCell = Arc<tokio::sync::OnceCell<Vec<String>>>;
+
+/// Lets simultaneous identical `/stats` requests share one database query.
+/// A query joins one already in flight, but results aren't kept once it
+/// finishes.
+#[derive(Default)]
+pub struct StatsQueries {
+    in_flight: Mutex<HashMap<StatsQuery, StatsQueryCell>>,
+}
+
+impl StatsQueries {
+    async fn run(&self, pool: &PgPool, query: &StatsQuery) -> Result<Vec<String>, ApiError> {
+        let cell = Arc::clone(
+            self.in_flight
+                .lock()
+                .unwrap()
+                .entry(query.clone())
+                .or_default(),
+        );
+        // A failed query isn't shared; the next waiter runs its own.
+        let result = cell
+            .get_or_try_init(|| get_last_cities(pool, query))
+            .await
+            .cloned();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(query)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(query);
+        }
+        result
+    }
+}
+
+async fn get_last_cities(pool: &PgPool, params: &StatsQuery) -> Result<Vec<String>, ApiError> {
+    let query = format!(
+        "SELECT name FROM cities \
+         WHERE $2::timestamptz IS NULL OR EXISTS ( \
+             SELECT 1 FROM requests \
+             WHERE requests.city_id = cities.id AND requested_at > $2 \
+         ) \
+         ORDER BY {} LIMIT $1",
+        params.order.order_by()
+    );
+    let cities = sqlx::query_scalar(&query)
+        .bind(params.limit)
+        .bind(params.since)
+        .fetch_all(pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+    Ok(cities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{seed_cities, unreachable_pool};
+
+    fn stats_query(query: &str) -> StatsQuery {
+        let uri: axum::http::Uri = format!("/stats?{query}").parse().unwrap();
+        Query::<StatsQuery>::try_from_uri(&uri).unwrap().0
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_orders_by_insertion_by_default(pool: PgPool) {
+        seed_cities(&pool).await;
+
+        let cities = get_last_cities(&pool, &stats_query("")).await.unwrap();
+
+        assert_eq!(cities, ["Rome", "Paris", "Berlin"]);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_orders_by_request_frequency(pool: PgPool) {
+        seed_cities(&pool).await;
+
+        let cities = get_last_cities(&pool, &stats_query("order=frequency"))
+            .await
+            .unwrap();
+
+        assert_eq!(cities, ["Paris", "Berlin", "Rome"]);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_orders_by_last_request(pool: PgPool) {
+        seed_cities(&pool).await;
+
+        let cities = get_last_cities(&pool, &stats_query("order=recent"))
+            .await
+            .unwrap();
+
+        assert_eq!(cities, ["Rome", "Berlin", "Paris"]);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_lists_at_most_limit_cities(pool: PgPool) {
+        seed_cities(&pool).await;
+
+        let cities = get_last_cities(&pool, &stats_query("limit=2&order=frequency"))
+            .await
+            .unwrap();
+
+        assert_eq!(cities, ["Paris", "Berlin"]);
+    }
+
+    #[test]
+    fn stats_rejects_unknown_orders() {
+        let uri: axum::http::Uri = "/stats?order=popular".parse().unwrap();
+
+        assert!(Query::<StatsQuery>::try_from_uri(&uri).is_err());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_rejects_limits_out_of_range(pool: PgPool) {
+        let state = Arc::new(AppState::for_tests(pool));
+
+        for limit in [0, MAX_STATS_LIMIT + 1] {
+            let query = stats_query(&format!("limit={limit}"));
+            let result = stats(User, Query(query), State(Arc::clone(&state))).await;
+
+            assert!(matches!(result, Err(ApiError::BadRequest(_))), "{limit}");
+        }
+    }
+
+    /// Adds a request for each `(city, requested_at)` to the history.
+    async fn seed_requests(pool: &PgPool, requests: &[(&str, &str)]) {
+        for (city, requested_at) in requests {
+            sqlx::query(
+                "INSERT INTO requests (city_id, requested_at) \
+                 SELECT id, $2::timestamptz FROM cities WHERE name = $1",
+            )
+            .bind(city)
+            .bind(requested_at)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_since_lists_only_cities_requested_after_it(pool: PgPool) {
+        seed_cities(&pool).await;
+        // Paris's `last_requested_at` is 08:00, but the history is what counts.
+        seed_requests(
+            &pool,
+            &[
+                ("Berlin", "2024-05-01T08:00:00Z"),
+                ("Paris", "2024-05-01T09:30:00Z"),
+                ("Berlin", "2024-05-01T10:30:00Z"),
+                ("Rome", "2024-05-01T07:00:00Z"),
+            ],
+        )
+        .await;
+
+        let since_nine = get_last_cities(&pool, &stats_query("since=2024-05-01T09:00:00Z"))
+            .await
+            .unwrap();
+        let since_ten = get_last_cities(&pool, &stats_query("since=2024-05-01T10:00:00Z"))
+            .await
+            .unwrap();
+        let with_offset = get_last_cities(
+            &pool,
+            &stats_query("since=2024-05-01T11:00:00%2B02:00&order=frequency"),
+        )
+        .await
+        .unwrap();
+        let since_eleven = get_last_cities(&pool, &stats_query("since=2024-05-01T11:00:00Z"))
+            .await
+            .unwrap();
+
+        assert_eq!(since_nine, ["Paris", "Berlin"]);
+        assert_eq!(since_ten, ["Berlin"]);
+        assert_eq!(with_offset, ["Paris", "Berlin"]);
+        assert!(since_eleven.is_empty());
+    }
+
+    #[test]
+    fn stats_parses_since_into_a_utc_timestamp() {
+        let query = stats_query("since=2024-05-01T14:00:00.5%2B02:00");
+
+        let expected: DateTime<Utc> = "2024-05-01T12:00:00.5Z".parse().unwrap();
+        assert_eq!(query.since, Some(expected));
+        assert_eq!(stats_query("").since, None);
+    }
+
+    #[test]
+    fn stats_rejects_a_malformed_since() {
+        for since in [
+            "yesterday",
+            "2024-05-01",
+            "2024-05-01T09:00:00",
+            "2024-13-01T09:00:00Z",
+            "2024-02-30T09:00:00Z",
+            "2024-05-01T25:00:00Z",
+            "2024-05-01T09:00:00%2B24:00",
+        ] {
+            let uri: axum::http::Uri = format!("/stats?since={since}").parse().unwrap();
+
+            let rejection = Query::<StatsQuery>::try_from_uri(&uri).err();
+
+            let message = rejection.map(|r| r.body_text()).unwrap_or_default();
+            assert!(message.contains("RFC 3339"), "{since}: {message}");
+        }
+    }
+
+    /// How many of this database's connections are waiting for a lock.
+    async fn waiting_for_locks(pool: &PgPool) -> i64 {
+        sqlx::query_scalar(
+            "SELECT count(*) FROM pg_stat_activity \
+             WHERE datname = current_database() AND wait_event_type = 'Lock'",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn simultaneous_identical_stats_requests_share_one_query(pool: PgPool) {
+        seed_cities(&pool).await;
+        let state = Arc::new(AppState::for_tests(pool.clone()));
+        // Keeps every stats query waiting in the database until rolled back.
+        let mut lock = pool.begin().await.unwrap();
+        sqlx::query("LOCK TABLE cities IN ACCESS EXCLUSIVE MODE")
+            .execute(&mut *lock)
+            .await
+            .unwrap();
+
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..5 {
+            let state = Arc::clone(&state);
+            requests.spawn(async move {
+                let query = stats_query("");
+                state.stats_queries.run(&state.pool, &query).await
+            });
+        }
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while waiting_for_locks(&pool).await == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("no stats query reached the database");
+        // Give any query that wasn't shared time to reach the database too.
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let queries = waiting_for_locks(&pool).await;
+        lock.rollback().await.unwrap();
+
+        assert_eq!(queries, 1);
+        while let Some(result) = requests.join_next().await {
+            assert_eq!(result.unwrap().unwrap(), ["Rome", "Paris", "Berlin"]);
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_reads_from_the_replica_when_one_is_configured(pool: PgPool) {
+        seed_cities(&pool).await;
+        let state = AppState {
+            pool: unreachable_pool(),
+            replica: Some(pool.clone()),
+            ..AppState::for_tests(pool)
+        };
+
+        let Html(html) = stats(User, Query(stats_query("")), State(Arc::new(state)))
+            .await
+            .unwrap();
+
+        assert!(html.contains("<li>Rome</li>"), "{html}");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn stats_reads_from_the_primary_without_a_replica(pool: PgPool) {
+        seed_cities(&pool).await;
+
+        let Html(html) = stats(
+            User,
+            Query(stats_query("")),
+            State(Arc::new(AppState::for_tests(pool))),
+        )
+        .await
+        .unwrap();
+
+        assert!(html.contains("<li>Rome</li>"), "{html}");
+    }
+}
@@ -0,0 +1,43 @@
+//! Fixtures shared by the tests of block 6's modules.
+
+use sqlx::PgPool;
+
+use crate::cities::LatLong;
+
+/// Berlin, Paris and Rome, inserted in that order, with Paris requested
+/// most often and Rome most recently.
+pub async fn seed_cities(pool: &PgPool) {
+    sqlx::query(
+        "INSERT INTO cities (name, latitude, longitude, request_count, last_requested_at) \
+         VALUES ('Berlin', 52.52, 13.41, 5, '2024-05-01T10:00:00Z'), \
+                ('Paris', 48.85, 2.35, 9, '2024-05-01T08:00:00Z'), \
+                ('Rome', 41.89, 12.48, 1, '2024-05-01T12:00:00Z')",
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// A pool whose connections always fail, quickly, standing in for a
+/// database a test must not touch.
+pub fn unreachable_pool() -> PgPool {
+    sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_millis(500))
+        .connect_lazy("postgres://localhost:1/unreachable")
+        .unwrap()
+}
+
+pub fn berlin() -> LatLong {
+    LatLong {
+        latitude: 52.52,
+        longitude: 13.41,
+    }
+}
+
+pub async fn request_count(pool: &PgPool, city: &str) -> Option<i64> {
+    sqlx::query_scalar("SELECT request_count FROM cities WHERE name = $1")
+        .bind(city)
+        .fetch_optional(pool)
+        .await
+        .unwrap()
+}
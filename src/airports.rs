@@ -0,0 +1,73 @@
+use serde::Serialize;
+
+use crate::LatLong;
+
+/// A bundled airport entry, looked up by either its ICAO or IATA code.
+/// Serializes as its codes and name; the forecast carries the coordinates.
+#[derive(Serialize)]
+pub struct Airport {
+    pub icao: &'static str,
+    pub iata: &'static str,
+    pub name: &'static str,
+    #[serde(skip)]
+    pub latitude: f64,
+    #[serde(skip)]
+    pub longitude: f64,
+}
+
+impl Airport {
+    pub fn lat_long(&self) -> LatLong {
+        LatLong {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        }
+    }
+}
+
+/// Returns `true` for three-letter IATA or four-letter ICAO codes.
+pub fn is_valid_code(code: &str) -> bool {
+    matches!(code.len(), 3 | 4) && code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+pub fn lookup(code: &str) -> Option<&'static Airport> {
+    let code = code.to_ascii_uppercase();
+    AIRPORTS
+        .iter()
+        .find(|airport| airport.icao == code || airport.iata == code)
+}
+
+#[rustfmt::skip]
+static AIRPORTS: &[Airport] = &[
+    Airport { icao: "KSFO", iata: "SFO", name: "San Francisco International Airport", latitude: 37.6190, longitude: -122.3749 },
+    Airport { icao: "KJFK", iata: "JFK", name: "John F. Kennedy International Airport", latitude: 40.6398, longitude: -73.7789 },
+    Airport { icao: "KLAX", iata: "LAX", name: "Los Angeles International Airport", latitude: 33.9425, longitude: -118.4081 },
+    Airport { icao: "KORD", iata: "ORD", name: "Chicago O'Hare International Airport", latitude: 41.9786, longitude: -87.9048 },
+    Airport { icao: "KATL", iata: "ATL", name: "Hartsfield-Jackson Atlanta International Airport", latitude: 33.6367, longitude: -84.4281 },
+    Airport { icao: "KSEA", iata: "SEA", name: "Seattle-Tacoma International Airport", latitude: 47.4490, longitude: -122.3093 },
+    Airport { icao: "KDEN", iata: "DEN", name: "Denver International Airport", latitude: 39.8617, longitude: -104.6731 },
+    Airport { icao: "KBOS", iata: "BOS", name: "Boston Logan International Airport", latitude: 42.3643, longitude: -71.0052 },
+    Airport { icao: "CYYZ", iata: "YYZ", name: "Toronto Pearson International Airport", latitude: 43.6772, longitude: -79.6306 },
+    Airport { icao: "EGLL", iata: "LHR", name: "London Heathrow Airport", latitude: 51.4706, longitude: -0.4619 },
+    Airport { icao: "EGKK", iata: "LGW", name: "London Gatwick Airport", latitude: 51.1481, longitude: -0.1903 },
+    Airport { icao: "LFPG", iata: "CDG", name: "Paris Charles de Gaulle Airport", latitude: 49.0097, longitude: 2.5479 },
+    Airport { icao: "EDDF", iata: "FRA", name: "Frankfurt Airport", latitude: 50.0333, longitude: 8.5706 },
+    Airport { icao: "EDDM", iata: "MUC", name: "Munich Airport", latitude: 48.3538, longitude: 11.7861 },
+    Airport { icao: "EHAM", iata: "AMS", name: "Amsterdam Airport Schiphol", latitude: 52.3086, longitude: 4.7639 },
+    Airport { icao: "LEMD", iata: "MAD", name: "Adolfo Suarez Madrid-Barajas Airport", latitude: 40.4719, longitude: -3.5626 },
+    Airport { icao: "LIRF", iata: "FCO", name: "Rome Fiumicino Airport", latitude: 41.8003, longitude: 12.2389 },
+    Airport { icao: "LSZH", iata: "ZRH", name: "Zurich Airport", latitude: 47.4647, longitude: 8.5492 },
+    Airport { icao: "LOWW", iata: "VIE", name: "Vienna International Airport", latitude: 48.1103, longitude: 16.5697 },
+    Airport { icao: "EKCH", iata: "CPH", name: "Copenhagen Airport", latitude: 55.6179, longitude: 12.6560 },
+    Airport { icao: "ESSA", iata: "ARN", name: "Stockholm Arlanda Airport", latitude: 59.6519, longitude: 17.9186 },
+    Airport { icao: "ENGM", iata: "OSL", name: "Oslo Airport, Gardermoen", latitude: 60.1939, longitude: 11.1004 },
+    Airport { icao: "EFHK", iata: "HEL", name: "Helsinki Airport", latitude: 60.3172, longitude: 24.9633 },
+    Airport { icao: "EIDW", iata: "DUB", name: "Dublin Airport", latitude: 53.4213, longitude: -6.2701 },
+    Airport { icao: "OMDB", iata: "DXB", name: "Dubai International Airport", latitude: 25.2528, longitude: 55.3644 },
+    Airport { icao: "RJTT", iata: "HND", name: "Tokyo Haneda Airport", latitude: 35.5523, longitude: 139.7798 },
+    Airport { icao: "RJAA", iata: "NRT", name: "Narita International Airport", latitude: 35.7647, longitude: 140.3864 },
+    Airport { icao: "WSSS", iata: "SIN", name: "Singapore Changi Airport", latitude: 1.3502, longitude: 103.9944 },
+    Airport { icao: "VHHH", iata: "HKG", name: "Hong Kong International Airport", latitude: 22.3089, longitude: 113.9146 },
+    Airport { icao: "YSSY", iata: "SYD", name: "Sydney Kingsford Smith Airport", latitude: -33.9461, longitude: 151.1772 },
+    Airport { icao: "SBGR", iata: "GRU", name: "Sao Paulo/Guarulhos International Airport", latitude: -23.4356, longitude: -46.4731 },
+    Airport { icao: "FAOR", iata: "JNB", name: "O. R. Tambo International Airport", latitude: -26.1392, longitude: 28.2460 },
+];
@@ -0,0 +1,73 @@
+use serde::Serialize;
+
+/// Credit for a data provider, as required by its license.
+#[derive(Serialize, Debug, Clone)]
+pub struct Attribution {
+    /// What the provider supplied, e.g. `"forecast"`.
+    pub role: &'static str,
+    pub text: String,
+    pub url: String,
+}
+
+impl Attribution {
+    /// Reads `{prefix}_ATTRIBUTION` and `{prefix}_ATTRIBUTION_URL`, so that a
+    /// deployment using another provider can credit it correctly.
+    pub fn from_env(role: &'static str, prefix: &str, text: &str, url: &str) -> Self {
+        let var = |suffix: &str| std::env::var(format!("{prefix}_{suffix}")).ok();
+        Self {
+            role,
+            text: var("ATTRIBUTION").unwrap_or_else(|| text.to_string()),
+            url: var("ATTRIBUTION_URL").unwrap_or_else(|| url.to_string()),
+        }
+    }
+}
+
+/// Open-Meteo data is licensed under CC BY 4.0.
+const OPEN_METEO_LICENSE: &str = "https://creativecommons.org/licenses/by/4.0/";
+
+pub fn forecast_from_env() -> Attribution {
+    Attribution::from_env(
+        "forecast",
+        "FORECAST",
+        "Weather data by Open-Meteo.com (CC BY 4.0)",
+        OPEN_METEO_LICENSE,
+    )
+}
+
+pub fn geocoding_from_env() -> Attribution {
+    Attribution::from_env(
+        "geocoding",
+        "GEOCODING",
+        "Geocoding by Open-Meteo.com (CC BY 4.0)",
+        OPEN_METEO_LICENSE,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_environment_overrides_the_default_credit() {
+        // A prefix of its own, so no other test sees these variables.
+        std::env::set_var("ATTRIBUTION_TEST_ATTRIBUTION", "Geocoding by Nominatim");
+        std::env::set_var(
+            "ATTRIBUTION_TEST_ATTRIBUTION_URL",
+            "https://osm.org/copyright",
+        );
+
+        let attribution = Attribution::from_env("geocoding", "ATTRIBUTION_TEST", "Default", "");
+
+        assert_eq!(attribution.text, "Geocoding by Nominatim");
+        assert_eq!(attribution.url, "https://osm.org/copyright");
+    }
+
+    #[test]
+    fn open_meteo_is_credited_by_default() {
+        let attribution = Attribution::from_env("forecast", "UNSET_PROVIDER", "Open-Meteo", "x");
+
+        assert_eq!(attribution.role, "forecast");
+        assert_eq!(attribution.text, "Open-Meteo");
+        assert_eq!(attribution.url, "x");
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::{error::ApiError, AppState};
+
+/// Bounds how many requests are handled at once. Requests over the limit
+/// wait in a queue of bounded depth; once that is full too they are turned
+/// away with a `503`, rather than piling onto the database and upstream.
+pub struct Admission {
+    permits: Semaphore,
+    queue_depth: usize,
+    waiting: AtomicUsize,
+}
+
+impl Admission {
+    pub fn new(max_concurrent: usize, queue_depth: usize) -> Self {
+        Self {
+            permits: Semaphore::new(max_concurrent),
+            queue_depth,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a free slot, or fails straight away if the queue is full.
+    async fn admit(&self) -> Result<SemaphorePermit<'_>, ApiError> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(permit);
+        }
+        // Leaves the queue however the wait ends, including when the request
+        // is dropped because the client went away.
+        let _queued = Queued::join(self)?;
+        let permit = self.permits.acquire().await;
+        Ok(permit.expect("the admission semaphore is never closed"))
+    }
+}
+
+/// A place in the [`Admission`] queue, given up on drop.
+struct Queued<'a>(&'a Admission);
+
+impl<'a> Queued<'a> {
+    fn join(admission: &'a Admission) -> Result<Self, ApiError> {
+        let queued = Self(admission);
+        if admission.waiting.fetch_add(1, Ordering::SeqCst) >= admission.queue_depth {
+            return Err(ApiError::Overloaded);
+        }
+        Ok(queued)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn limit_concurrency(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(admission) = state.admission.as_deref() else {
+        return next.run(req).await;
+    };
+    let _permit = match admission.admit().await {
+        Ok(permit) => permit,
+        Err(e) => return e.into_response(),
+    };
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{self, MockUpstream, TestApp};
+
+    #[tokio::test]
+    async fn a_flood_beyond_the_queue_is_turned_away_while_queued_requests_succeed() {
+        let mock =
+            MockUpstream::start_with_delay(Duration::from_millis(200), test_support::open_meteo)
+                .await;
+        let mut config = Config::from_env();
+        config.max_concurrent_requests = 1;
+        config.request_queue_depth = 2;
+        let app = TestApp::serve(test_support::state(config, &mock)).await;
+
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..6 {
+            requests.spawn(
+                app.request(reqwest::Method::GET, "/weather?city=Berlin")
+                    .send(),
+            );
+        }
+        let mut statuses = Vec::new();
+        while let Some(response) = requests.join_next().await {
+            statuses.push(response.unwrap().unwrap().status());
+        }
+
+        let count = |status| statuses.iter().filter(|&&s| s == status).count();
+        assert_eq!(count(StatusCode::OK), 3, "{statuses:?}");
+        assert_eq!(count(StatusCode::SERVICE_UNAVAILABLE), 3, "{statuses:?}");
+    }
+
+    /// Whether `future` is still waiting after a moment.
+    async fn is_pending<F: std::future::Future + Unpin>(future: &mut F) -> bool {
+        tokio::time::timeout(Duration::from_millis(20), future)
+            .await
+            .is_err()
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_wait_gives_its_place_in_the_queue_back() {
+        let admission = Admission::new(1, 1);
+        let busy = admission.admit().await.unwrap();
+        let mut waiting = Box::pin(admission.admit());
+        assert!(is_pending(&mut waiting).await);
+        assert!(matches!(admission.admit().await, Err(ApiError::Overloaded)));
+
+        drop(waiting);
+
+        assert_eq!(admission.waiting.load(Ordering::SeqCst), 0);
+        let mut next = Box::pin(admission.admit());
+        assert!(is_pending(&mut next).await);
+        drop(busy);
+        assert!(next.await.is_ok());
+    }
+}
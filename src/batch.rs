@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::HeaderMap, Json};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::OnceCell, task::JoinSet};
+
+use crate::{
+    cache::city_key, check_allowed, city::City, error::ApiError, fetch_weather_many,
+    forecast::ForecastOptions, get_location, units::TemperatureUnit, AppState, LatLong,
+    WeatherResponse,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const MAX_BATCH_CITIES: usize = 20;
+
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    cities: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BatchResult {
+    city: City,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weather: Option<WeatherResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+type Slot = Arc<OnceCell<Vec<BatchResult>>>;
+
+/// Remembers batch responses by `Idempotency-Key` so that a retried request
+/// gets the original response back instead of being processed again.
+///
+/// A key is reserved before the batch runs, so a retry arriving while the
+/// original is still in flight waits for its response rather than running
+/// the batch a second time.
+pub struct IdempotencyStore {
+    ttl: Duration,
+    /// Per key, when it was reserved and the response once there is one.
+    responses: Mutex<HashMap<String, (Instant, Slot)>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The slot holding the response for `key`, reserving a fresh one unless
+    /// the key is in flight or answered within the TTL.
+    fn reserve(&self, key: &str) -> Slot {
+        let mut responses = self.responses.lock().unwrap();
+        responses.retain(|_, (reserved_at, slot)| {
+            reserved_at.elapsed() < self.ttl
+                // Still running, or waited on by someone.
+                || (!slot.initialized() && Arc::strong_count(slot) > 1)
+        });
+        let (_, slot) = responses
+            .entry(key.to_string())
+            .or_insert_with(|| (Instant::now(), Slot::default()));
+        Arc::clone(slot)
+    }
+}
+
+/// `POST /weather/batch`: fetches the forecast for several cities at once.
+/// Failures are reported per city rather than failing the whole batch.
+pub async fn batch_weather(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BatchRequest>,
+) -> Result<Json<Vec<BatchResult>>, ApiError> {
+    let key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let cities = parse_cities(request.cities)?;
+    let Some(key) = key else {
+        return Ok(Json(
+            weather_for_cities(&state, cities, TemperatureUnit::Celsius).await,
+        ));
+    };
+
+    // If the request holding the reservation is cancelled, the next one
+    // waiting on the slot runs the batch instead.
+    let slot = state.idempotency.reserve(key);
+    let results = slot
+        .get_or_init(|| weather_for_cities(&state, cities, TemperatureUnit::Celsius))
+        .await;
+    Ok(Json(results.clone()))
+}
+
+/// Validates the cities of a batch: their number and each name.
+pub fn parse_cities(cities: Vec<String>) -> Result<Vec<City>, ApiError> {
+    if cities.is_empty() || cities.len() > MAX_BATCH_CITIES {
+        return Err(ApiError::BadRequest(format!(
+            "A batch must contain between 1 and {MAX_BATCH_CITIES} cities"
+        )));
+    }
+    cities
+        .into_iter()
+        .map(City::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::BadRequest)
+}
+
+/// Geocodes the cities concurrently, but never more than the configured
+/// `batch_concurrency` at a time. Results are in the order of `cities`.
+///
+/// A city listed more than once (in any spelling `city_key` considers the
+/// same) is geocoded once, and the result reused for the other mentions.
+/// Cities outside the allowlist resolve to [`ApiError::Forbidden`].
+async fn resolve_cities(state: &AppState, cities: &[City]) -> Vec<Result<LatLong, ApiError>> {
+    let limit = state.config.batch_concurrency;
+    let mut resolved: Vec<Option<Result<LatLong, ApiError>>> = vec![None; cities.len()];
+    // For every city, the index of its first mention.
+    let mut first_mention: HashMap<String, usize> = HashMap::new();
+    let mut mentions = Vec::with_capacity(cities.len());
+    let mut tasks = JoinSet::new();
+    for (index, city) in cities.iter().enumerate() {
+        let first = *first_mention.entry(city_key(city)).or_insert(index);
+        mentions.push(first);
+        if first != index {
+            continue;
+        }
+        // Cities this deployment doesn't serve fail on their own, like any
+        // other per-city error, and are never geocoded.
+        if let Err(e) = check_allowed(state, city) {
+            resolved[index] = Some(Err(e));
+            continue;
+        }
+        if tasks.len() == limit {
+            let (done, lat_long) = next_resolved(&mut tasks).await;
+            resolved[done] = Some(lat_long);
+        }
+        let state = state.clone();
+        let city = city.clone();
+        tasks.spawn(async move {
+            let lat_long = get_location(&state, &city)
+                .await
+                .map(|location| location.lat_long);
+            (index, lat_long)
+        });
+    }
+    while !tasks.is_empty() {
+        let (done, lat_long) = next_resolved(&mut tasks).await;
+        resolved[done] = Some(lat_long);
+    }
+    // Like the single-city routes, only cities that resolved go in the
+    // history, once per mention.
+    cities
+        .iter()
+        .zip(mentions)
+        .map(|(city, first)| {
+            let lat_long = resolved[first]
+                .clone()
+                .expect("every first mention was resolved");
+            if lat_long.is_ok() {
+                state.history.record(city);
+            }
+            lat_long
+        })
+        .collect()
+}
+
+async fn next_resolved(
+    tasks: &mut JoinSet<(usize, Result<LatLong, ApiError>)>,
+) -> (usize, Result<LatLong, ApiError>) {
+    match tasks.join_next().await {
+        Some(Ok(resolved)) => resolved,
+        Some(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+        None => unreachable!("only called while tasks are running"),
+    }
+}
+
+/// Fetches the forecast for each city in `unit`, reporting failures per city.
+pub async fn weather_for_cities(
+    state: &AppState,
+    cities: Vec<City>,
+    unit: TemperatureUnit,
+) -> Vec<BatchResult> {
+    // Resolve every city first, then fetch all forecasts in one upstream call.
+    let resolved = resolve_cities(state, &cities).await;
+    let locations: Vec<LatLong> = resolved
+        .iter()
+        .filter_map(|lat_long| lat_long.as_ref().ok().cloned())
+        .collect();
+    let mut forecasts = if locations.is_empty() {
+        Vec::new()
+    } else {
+        match fetch_weather_many(&state.upstream, &locations, &ForecastOptions::default()).await {
+            Ok(forecasts) => forecasts,
+            // Every resolved city shares the failed request.
+            Err(e) => locations.iter().map(|_| Err(e.clone())).collect(),
+        }
+    }
+    .into_iter();
+
+    cities
+        .into_iter()
+        .zip(resolved)
+        .map(|(city, lat_long)| {
+            let result =
+                lat_long.and_then(|_| forecasts.next().expect("one forecast per resolved city"));
+            match result {
+                Ok(mut weather) => {
+                    weather.convert_temperatures(unit);
+                    BatchResult {
+                        city,
+                        weather: Some(weather),
+                        error: None,
+                    }
+                }
+                Err(e) => BatchResult {
+                    city,
+                    weather: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_retry_shares_the_reservation_of_its_key() {
+        let store = IdempotencyStore::new(Duration::from_secs(60));
+
+        let original = store.reserve("key");
+
+        assert!(Arc::ptr_eq(&original, &store.reserve("key")));
+        assert!(!Arc::ptr_eq(&original, &store.reserve("other key")));
+    }
+
+    #[test]
+    fn a_key_in_flight_is_kept_past_the_ttl() {
+        let store = IdempotencyStore::new(Duration::ZERO);
+
+        let original = store.reserve("key");
+
+        assert!(Arc::ptr_eq(&original, &store.reserve("key")));
+    }
+
+    #[test]
+    fn an_answered_key_expires_after_the_ttl() {
+        let store = IdempotencyStore::new(Duration::ZERO);
+        let original = store.reserve("key");
+        assert!(original.set(Vec::new()).is_ok());
+
+        assert!(!Arc::ptr_eq(&original, &store.reserve("key")));
+    }
+
+    #[test]
+    fn a_batch_needs_between_one_and_the_maximum_cities() {
+        let cities = |n: usize| (0..n).map(|i| format!("City {i}")).collect();
+
+        assert!(parse_cities(cities(0)).is_err());
+        assert_eq!(
+            parse_cities(cities(MAX_BATCH_CITIES)).unwrap().len(),
+            MAX_BATCH_CITIES
+        );
+        assert!(parse_cities(cities(MAX_BATCH_CITIES + 1)).is_err());
+    }
+
+    #[test]
+    fn every_city_of_a_batch_is_validated() {
+        let cities = vec!["Berlin".to_string(), "Ber\u{0}lin".to_string()];
+
+        assert!(matches!(parse_cities(cities), Err(ApiError::BadRequest(_))));
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+
+use crate::{error::ApiError, LatLong};
+
+/// Number of independently locked partitions of the in-memory tier.
+const SHARDS: usize = 16;
+
+/// Storage for geocoding results, keyed by city.
+///
+/// The default backend keeps entries in memory and sled, which only works
+/// for a single instance. Deployments running several instances can share
+/// entries through Redis instead.
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    async fn get(&self, city: &str) -> Result<Option<CacheEntry>, ApiError>;
+    async fn set(&self, city: &str, entry: CacheEntry) -> Result<(), ApiError>;
+    async fn remove(&self, city: &str) -> Result<(), ApiError>;
+    /// Removes every entry and returns how many there were.
+    async fn clear(&self) -> Result<usize, ApiError>;
+    /// Every cached entry together with its city name, in no particular order.
+    async fn entries(&self) -> Result<Vec<(String, CacheEntry)>, ApiError>;
+}
+
+/// Two-tier geocoding cache: an in-memory map in front of the sled database.
+///
+/// Lookups check memory first, then sled, promoting sled hits into memory.
+/// Inserts write through to both tiers.
+///
+/// The memory tier is split into shards so that cities hashing to different
+/// shards never contend on the same lock.
+pub struct GeoCache {
+    shards: Vec<RwLock<HashMap<String, CacheEntry>>>,
+    db: sled::Db,
+}
+
+impl GeoCache {
+    pub fn new(db: sled::Db) -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            db,
+        }
+    }
+
+    fn shard(&self, city: &str) -> &RwLock<HashMap<String, CacheEntry>> {
+        let mut hasher = DefaultHasher::new();
+        city.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+}
+
+#[async_trait]
+impl CacheBackend for GeoCache {
+    async fn get(&self, city: &str) -> Result<Option<CacheEntry>, ApiError> {
+        if let Some(entry) = self.shard(city).read().unwrap().get(city) {
+            return Ok(Some(entry.clone()));
+        }
+
+        let Some(bytes) = self.db.get(city.as_bytes()).map_err(db_error)? else {
+            return Ok(None);
+        };
+        let entry: CacheEntry = serde_json::from_slice(&bytes).map_err(db_error)?;
+        self.shard(city)
+            .write()
+            .unwrap()
+            .insert(city.to_string(), entry.clone());
+        Ok(Some(entry))
+    }
+
+    async fn set(&self, city: &str, entry: CacheEntry) -> Result<(), ApiError> {
+        let bytes = serde_json::to_vec(&entry).map_err(db_error)?;
+        self.db.insert(city.as_bytes(), bytes).map_err(db_error)?;
+        self.shard(city)
+            .write()
+            .unwrap()
+            .insert(city.to_string(), entry);
+        Ok(())
+    }
+
+    async fn remove(&self, city: &str) -> Result<(), ApiError> {
+        self.db.remove(city.as_bytes()).map_err(db_error)?;
+        self.shard(city).write().unwrap().remove(city);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<usize, ApiError> {
+        // Every sled entry has been in memory or can be, so sled has the count.
+        let removed = self.db.len();
+        self.db.clear().map_err(db_error)?;
+        for shard in &self.shards {
+            shard.write().unwrap().clear();
+        }
+        Ok(removed)
+    }
+
+    async fn entries(&self) -> Result<Vec<(String, CacheEntry)>, ApiError> {
+        // Inserts write through, so sled holds everything memory does.
+        entries(&self.db).map_err(db_error)
+    }
+}
+
+/// Connects the Redis cache backend, available when built with the `redis`
+/// feature.
+#[cfg(feature = "redis")]
+pub async fn redis_backend(url: &str) -> Result<Arc<dyn CacheBackend>, String> {
+    let cache = crate::redis_cache::RedisCache::connect(url)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Arc::new(cache))
+}
+
+#[cfg(not(feature = "redis"))]
+pub async fn redis_backend(_url: &str) -> Result<Arc<dyn CacheBackend>, String> {
+    Err("this build has no Redis support; rebuild with `--features redis`".to_string())
+}
+
+/// Serializes cache misses per city, so concurrent requests for one city
+/// geocode once, while misses for other cities proceed in parallel.
+///
+/// This is local to the process regardless of the cache backend.
+#[derive(Default)]
+pub struct CityLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl CityLocks {
+    pub async fn lock(&self, city: &str) -> CityGuard<'_> {
+        let lock = Arc::clone(
+            self.locks
+                .lock()
+                .unwrap()
+                .entry(city.to_string())
+                .or_default(),
+        );
+        CityGuard {
+            city_locks: &self.locks,
+            city: city.to_string(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+/// Held while a city is being geocoded; see [`CityLocks::lock`].
+pub struct CityGuard<'a> {
+    city_locks: &'a Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    city: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for CityGuard<'_> {
+    fn drop(&mut self) {
+        self.guard.take();
+        // Forget the lock once nobody else holds or waits for it.
+        let mut locks = self.city_locks.lock().unwrap();
+        if locks
+            .get(&self.city)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.city);
+        }
+    }
+}
+
+/// Counts per city how often the geocoding cache answered a lookup, which is
+/// how many geocoding calls it saved.
+#[derive(Default)]
+pub struct HitCounter {
+    hits: Mutex<HashMap<String, u64>>,
+}
+
+impl HitCounter {
+    pub fn record(&self, city: &str) {
+        *self.hits.lock().unwrap().entry(city_key(city)).or_default() += 1;
+    }
+
+    /// Every city with its hit count, most hits first. Ties are listed
+    /// alphabetically.
+    pub fn ranking(&self) -> Vec<CityHits> {
+        let mut ranking: Vec<CityHits> = self
+            .hits
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(city, &hits)| CityHits {
+                city: city.clone(),
+                hits,
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.hits.cmp(&a.hits).then_with(|| a.city.cmp(&b.city)));
+        ranking
+    }
+}
+
+#[derive(Serialize)]
+pub struct CityHits {
+    pub city: String,
+    pub hits: u64,
+}
+
+/// Canonical form of a city name, so that `" New  York"` and `"new york"`
+/// refer to the same place.
+pub fn city_key(city: &str) -> String {
+    city.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+pub fn db_error(e: impl std::fmt::Display) -> ApiError {
+    ApiError::DatabaseError(e.to_string())
+}
+
+/// A geocoding result as stored in the sled cache.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CacheEntry {
+    #[serde(flatten)]
+    pub lat_long: LatLong,
+    /// When the entry was written. Entries cached before timestamps were
+    /// recorded don't have one.
+    #[serde(default)]
+    pub cached_at: Option<SystemTime>,
+    /// IANA timezone of the city, from geocoding or looked up by `/timezone`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl CacheEntry {
+    pub fn new(lat_long: LatLong) -> Self {
+        Self {
+            lat_long,
+            cached_at: Some(SystemTime::now()),
+            timezone: None,
+        }
+    }
+
+    /// Age of the entry in whole seconds, if it has a timestamp.
+    pub fn age_secs(&self, now: SystemTime) -> Option<u64> {
+        let cached_at = self.cached_at?;
+        Some(now.duration_since(cached_at).unwrap_or_default().as_secs())
+    }
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub total: usize,
+    pub oldest_age_secs: Option<u64>,
+    pub newest_age_secs: Option<u64>,
+    pub entries: Vec<EntryAge>,
+}
+
+#[derive(Serialize)]
+pub struct EntryAge {
+    pub city: String,
+    pub age_secs: Option<u64>,
+}
+
+/// Reads every cached geocoding entry together with its city name.
+pub fn entries(db: &sled::Db) -> Result<Vec<(String, CacheEntry)>, Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+    for item in db.iter() {
+        let (key, value) = item?;
+        let entry: CacheEntry = serde_json::from_slice(&value)?;
+        entries.push((String::from_utf8_lossy(&key).into_owned(), entry));
+    }
+    Ok(entries)
+}
+
+/// Deletes all but the `max_entries` most recently cached entries from disk
+/// and returns how many were deleted. Entries without a timestamp count as
+/// the oldest; ties are broken by city name, so the result is deterministic.
+pub fn trim_persisted(
+    db: &sled::Db,
+    max_entries: usize,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut entries = entries(db)?;
+    if entries.len() <= max_entries {
+        return Ok(0);
+    }
+    entries.sort_by(|(a_city, a), (b_city, b)| {
+        b.cached_at
+            .cmp(&a.cached_at)
+            .then_with(|| a_city.cmp(b_city))
+    });
+    let removed = entries.len() - max_entries;
+    for (city, _) in &entries[max_entries..] {
+        db.remove(city.as_bytes())?;
+    }
+    db.flush()?;
+    Ok(removed)
+}
+
+/// Summarizes the age of every cached geocoding entry, oldest first.
+pub fn stats(entries: Vec<(String, CacheEntry)>) -> CacheStats {
+    let now = SystemTime::now();
+    let mut entries: Vec<EntryAge> = entries
+        .into_iter()
+        .map(|(city, entry)| EntryAge {
+            city,
+            age_secs: entry.age_secs(now),
+        })
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.age_secs));
+
+    let ages = entries.iter().filter_map(|entry| entry.age_secs);
+    CacheStats {
+        total: entries.len(),
+        oldest_age_secs: ages.clone().max(),
+        newest_age_secs: ages.min(),
+        entries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn entry_aged(secs: u64) -> CacheEntry {
+        CacheEntry {
+            cached_at: Some(SystemTime::now() - Duration::from_secs(secs)),
+            ..CacheEntry::new(LatLong {
+                latitude: 52.52,
+                longitude: 13.41,
+            })
+        }
+    }
+
+    fn temporary_db() -> sled::Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    #[test]
+    fn entries_cached_before_timestamps_have_no_age() {
+        let entry: CacheEntry =
+            serde_json::from_str(r#"{"latitude":52.52,"longitude":13.41}"#).unwrap();
+
+        assert_eq!(entry.cached_at, None);
+        assert_eq!(entry.age_secs(SystemTime::now()), None);
+    }
+
+    #[test]
+    fn age_is_counted_in_whole_seconds_since_caching() {
+        let entry = entry_aged(0);
+        let later = entry.cached_at.unwrap() + Duration::from_millis(90_500);
+
+        assert_eq!(entry.age_secs(later), Some(90));
+    }
+
+    #[test]
+    fn stats_list_entries_oldest_first_with_untimed_ones_last() {
+        let mut untimed = entry_aged(0);
+        untimed.cached_at = None;
+
+        let stats = stats(vec![
+            ("Paris".to_string(), entry_aged(10)),
+            ("Legacy".to_string(), untimed),
+            ("Berlin".to_string(), entry_aged(300)),
+        ]);
+
+        let cities: Vec<&str> = stats.entries.iter().map(|e| e.city.as_str()).collect();
+        assert_eq!(cities, ["Berlin", "Paris", "Legacy"]);
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.oldest_age_secs, Some(300));
+        assert_eq!(stats.newest_age_secs, Some(10));
+    }
+
+    fn persisted_cities(db: &sled::Db) -> Vec<String> {
+        let mut cities: Vec<String> = entries(db).unwrap().into_iter().map(|(c, _)| c).collect();
+        cities.sort();
+        cities
+    }
+
+    #[tokio::test]
+    async fn trimming_keeps_only_the_most_recent_entries_up_to_the_cap() {
+        let db = temporary_db();
+        let cache = GeoCache::new(db.clone());
+        for (city, age) in [("Berlin", 10), ("Paris", 400), ("Rome", 20), ("Oslo", 300)] {
+            cache.set(city, entry_aged(age)).await.unwrap();
+        }
+
+        let removed = trim_persisted(&db, 2).unwrap();
+
+        assert_eq!(removed, 2);
+        assert_eq!(persisted_cities(&db), ["Berlin", "Rome"]);
+    }
+
+    #[test]
+    fn trimming_drops_untimed_entries_first_and_breaks_ties_by_name() {
+        let db = temporary_db();
+        let timed = entry_aged(60);
+        let mut untimed = timed.clone();
+        untimed.cached_at = None;
+        for (city, entry) in [("Lima", &untimed), ("Cairo", &timed), ("Athens", &timed)] {
+            db.insert(city, serde_json::to_vec(entry).unwrap()).unwrap();
+        }
+
+        trim_persisted(&db, 1).unwrap();
+
+        assert_eq!(persisted_cities(&db), ["Athens"]);
+    }
+
+    #[test]
+    fn trimming_under_the_cap_deletes_nothing() {
+        let db = temporary_db();
+        db.insert("Berlin", serde_json::to_vec(&entry_aged(0)).unwrap())
+            .unwrap();
+
+        assert_eq!(trim_persisted(&db, 1).unwrap(), 0);
+        assert_eq!(persisted_cities(&db), ["Berlin"]);
+    }
+
+    #[test]
+    fn hits_are_ranked_by_count_then_name_under_one_spelling() {
+        let hits = HitCounter::default();
+        for city in ["Paris", "Berlin", " berlin ", "Rome", "BERLIN", "paris"] {
+            hits.record(city);
+        }
+
+        let ranking: Vec<(String, u64)> = hits
+            .ranking()
+            .into_iter()
+            .map(|c| (c.city, c.hits))
+            .collect();
+
+        assert_eq!(
+            ranking,
+            [
+                ("berlin".to_string(), 3),
+                ("paris".to_string(), 2),
+                ("rome".to_string(), 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn geo_cache_lists_every_stored_entry() {
+        let cache = GeoCache::new(temporary_db());
+        cache.set("Berlin", entry_aged(0)).await.unwrap();
+        cache.set("Paris", entry_aged(0)).await.unwrap();
+
+        let mut cities: Vec<String> = cache
+            .entries()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(city, _)| city)
+            .collect();
+        cities.sort();
+
+        assert_eq!(cities, ["Berlin", "Paris"]);
+    }
+
+    #[tokio::test]
+    async fn geo_cache_returns_what_was_set() {
+        let cache = GeoCache::new(temporary_db());
+
+        cache.set("Berlin", entry_aged(0)).await.unwrap();
+
+        let entry = cache.get("Berlin").await.unwrap().unwrap();
+        assert_eq!(entry.lat_long.latitude, 52.52);
+        assert!(cache.get("Paris").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn geo_cache_entries_survive_a_restart() {
+        let db = temporary_db();
+        GeoCache::new(db.clone())
+            .set("Berlin", entry_aged(0))
+            .await
+            .unwrap();
+
+        let restarted = GeoCache::new(db);
+
+        assert!(restarted.get("Berlin").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn geo_cache_promotes_sled_hits_into_memory() {
+        let db = temporary_db();
+        let cache = GeoCache::new(db.clone());
+        let bytes = serde_json::to_vec(&entry_aged(0)).unwrap();
+        db.insert("Berlin", bytes).unwrap();
+
+        assert!(cache.get("Berlin").await.unwrap().is_some());
+        assert!(cache.shard("Berlin").read().unwrap().contains_key("Berlin"));
+    }
+
+    #[tokio::test]
+    async fn memory_hits_do_not_read_sled() {
+        let db = temporary_db();
+        let cache = GeoCache::new(db.clone());
+        cache.set("Berlin", entry_aged(0)).await.unwrap();
+        cache.get("Berlin").await.unwrap();
+
+        db.remove("Berlin").unwrap();
+
+        assert!(cache.get("Berlin").await.unwrap().is_some());
+    }
+
+    #[test]
+    fn cities_are_spread_over_the_shards() {
+        let cache = GeoCache::new(temporary_db());
+
+        let used: HashSet<*const RwLock<HashMap<String, CacheEntry>>> = (0..1_000)
+            .map(|i| cache.shard(&format!("city-{i}")) as *const _)
+            .collect();
+
+        assert_eq!(used.len(), SHARDS);
+    }
+
+    #[tokio::test]
+    async fn the_memory_backend_works_through_the_trait_object() {
+        let cache: Arc<dyn CacheBackend> = Arc::new(GeoCache::new(temporary_db()));
+
+        cache.set("Berlin", entry_aged(0)).await.unwrap();
+        assert!(cache.get("Berlin").await.unwrap().is_some());
+        cache.remove("Berlin").await.unwrap();
+        assert!(cache.get("Berlin").await.unwrap().is_none());
+    }
+
+    #[cfg(not(feature = "redis"))]
+    #[tokio::test]
+    async fn the_redis_backend_needs_the_redis_feature() {
+        let error = redis_backend("redis://localhost").await.err().unwrap();
+
+        assert!(error.contains("--features redis"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn city_locks_serialize_one_city_but_not_others() {
+        let locks = CityLocks::default();
+        let berlin = locks.lock("Berlin").await;
+
+        let paris = tokio::time::timeout(Duration::from_millis(100), locks.lock("Paris")).await;
+        assert!(paris.is_ok());
+        let again = tokio::time::timeout(Duration::from_millis(100), locks.lock("Berlin")).await;
+        assert!(again.is_err());
+
+        drop(berlin);
+        let released = tokio::time::timeout(Duration::from_millis(100), locks.lock("Berlin")).await;
+        assert!(released.is_ok());
+    }
+
+    #[tokio::test]
+    async fn city_locks_are_forgotten_once_released() {
+        let locks = CityLocks::default();
+
+        drop(locks.lock("Berlin").await);
+
+        assert!(locks.locks.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn geo_cache_removes_and_clears_both_tiers() {
+        let db = temporary_db();
+        let cache = GeoCache::new(db.clone());
+        for city in ["Berlin", "Paris", "Rome"] {
+            cache.set(city, entry_aged(0)).await.unwrap();
+        }
+
+        cache.remove("Berlin").await.unwrap();
+        assert!(cache.get("Berlin").await.unwrap().is_none());
+
+        assert_eq!(cache.clear().await.unwrap(), 2);
+        assert!(cache.get("Paris").await.unwrap().is_none());
+        assert!(db.is_empty());
+    }
+}
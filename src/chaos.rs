@@ -0,0 +1,54 @@
+use crate::random::Sampler;
+
+/// Randomly fails a configured fraction of upstream calls, to exercise
+/// retries and error handling without a real outage.
+#[derive(Debug)]
+pub struct FailureInjector {
+    rate: f64,
+    sampler: Sampler,
+}
+
+impl FailureInjector {
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            sampler: Sampler::default(),
+        }
+    }
+
+    /// Whether the next call should fail. Always `false` at rate 0.
+    pub fn should_fail(&self) -> bool {
+        if self.rate <= 0.0 {
+            return false;
+        }
+        self.sampler.sample() < self.rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failures(injector: &FailureInjector, calls: usize) -> usize {
+        (0..calls).filter(|_| injector.should_fail()).count()
+    }
+
+    #[test]
+    fn rate_zero_never_fails_and_rate_one_always_does() {
+        assert_eq!(failures(&FailureInjector::new(0.0), 1000), 0);
+        assert_eq!(failures(&FailureInjector::new(1.0), 1000), 1000);
+    }
+
+    #[test]
+    fn rates_outside_zero_to_one_are_clamped() {
+        assert_eq!(failures(&FailureInjector::new(-0.5), 1000), 0);
+        assert_eq!(failures(&FailureInjector::new(7.0), 1000), 1000);
+    }
+
+    #[test]
+    fn roughly_the_configured_fraction_of_calls_fails() {
+        let failed = failures(&FailureInjector::new(0.5), 10_000);
+
+        assert!((4_000..=6_000).contains(&failed), "{failed}");
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+/// A city name as supplied by a client.
+///
+/// Construction rejects control and other invisible characters, so a `City`
+/// is always safe to log and to pass on to the geocoding API. Letters in any
+/// script, digits, spaces, hyphens, apostrophes and ordinary punctuation
+/// (`St. John's`, `48.85,2.35`) are accepted.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct City(String);
+
+impl City {
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl TryFrom<String> for City {
+    type Error = String;
+
+    fn try_from(city: String) -> Result<Self, Self::Error> {
+        if city.trim().is_empty() {
+            return Err("city must not be empty".to_string());
+        }
+        if let Some(c) = city.chars().find(|&c| !is_printable(c)) {
+            return Err(format!(
+                "city contains a non-printable character (U+{:04X})",
+                c as u32
+            ));
+        }
+        Ok(City(city))
+    }
+}
+
+/// Whether `c` renders as something visible (or as a plain space).
+fn is_printable(c: char) -> bool {
+    if c == ' ' {
+        return true;
+    }
+    !c.is_control()
+        && !c.is_whitespace()
+        && !matches!(
+            c,
+            // Zero-width and bidirectional formatting characters. The
+            // zero-width (non-)joiners are left alone, as some scripts
+            // (Persian among them) need them to spell names correctly.
+            '\u{200B}'
+                | '\u{200E}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{206F}'
+                | '\u{FEFF}'
+                | '\u{FFF9}'..='\u{FFFB}'
+        )
+}
+
+impl Deref for City {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for City {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn city(name: &str) -> Result<City, String> {
+        City::try_from(name.to_string())
+    }
+
+    #[test]
+    fn control_characters_are_rejected() {
+        for name in [
+            "Ber\nlin",
+            "Ber\0lin",
+            "Ber\tlin",
+            "Berlin\r",
+            "Ber\u{7F}lin",
+        ] {
+            let error = city(name).unwrap_err();
+
+            assert!(
+                error.starts_with("city contains a non-printable"),
+                "{name:?}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn invisible_formatting_characters_are_rejected() {
+        for name in [
+            "Ber\u{200B}lin",
+            "\u{202E}nilreB",
+            "Berlin\u{FEFF}",
+            "Ber\u{2028}lin",
+        ] {
+            assert!(city(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn the_offending_character_is_named() {
+        assert_eq!(
+            city("Ber\nlin").unwrap_err(),
+            "city contains a non-printable character (U+000A)"
+        );
+    }
+
+    #[test]
+    fn blank_names_are_rejected() {
+        assert_eq!(city("").unwrap_err(), "city must not be empty");
+        assert_eq!(city("   ").unwrap_err(), "city must not be empty");
+    }
+
+    #[test]
+    fn names_in_any_script_with_ordinary_punctuation_are_accepted() {
+        for name in [
+            "Zürich",
+            "São Paulo",
+            "St. John's",
+            "Winston-Salem",
+            "Kraków",
+            "東京",
+            "Αθήνα",
+            // Persian needs the zero-width non-joiner.
+            "کرمان\u{200C}شاه",
+            "48.85,2.35",
+        ] {
+            assert_eq!(city(name).as_deref(), Ok(name), "{name:?}");
+        }
+    }
+}
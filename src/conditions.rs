@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dates,
+    units::{self, PrecipitationUnit, WindSpeedUnit},
+    upstream::Api,
+    LatLong,
+};
+
+/// Most hours `/weather/conditions` returns: one week.
+pub const MAX_HOURS: usize = 168;
+
+/// The hourly variables requested for `/weather/conditions`.
+const VARIABLES: &str = "precipitation,snowfall,cloud_cover,relative_humidity_2m,wind_speed_10m";
+
+pub fn conditions_url(lat_long: &LatLong, timezone: &str) -> Url {
+    Api::Forecast.url([
+        ("latitude", lat_long.latitude.to_string()),
+        ("longitude", lat_long.longitude.to_string()),
+        ("hourly", VARIABLES.to_string()),
+        ("timezone", timezone.to_string()),
+        // One extra day so the window never runs past the end of the forecast.
+        ("forecast_days", (MAX_HOURS / 24 + 1).to_string()),
+    ])
+}
+
+pub fn validate_hours(hours: usize) -> Result<usize, String> {
+    if (1..=MAX_HOURS).contains(&hours) {
+        Ok(hours)
+    } else {
+        Err(format!("hours must be between 1 and {MAX_HOURS}"))
+    }
+}
+
+/// The forecast response for [`conditions_url`].
+#[derive(Deserialize, Debug)]
+pub struct ConditionsForecast {
+    #[serde(default)]
+    pub timezone: String,
+    /// Offset of `timezone` from UTC; hourly times are local to it.
+    #[serde(default)]
+    pub utc_offset_seconds: i64,
+    pub hourly: ConditionsSeries,
+    #[serde(default)]
+    pub hourly_units: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ConditionsSeries {
+    pub time: Vec<String>,
+    /// Rain, showers and snow together, in mm.
+    pub precipitation: Vec<f64>,
+    /// In cm.
+    pub snowfall: Vec<f64>,
+    /// In percent.
+    pub cloud_cover: Vec<f64>,
+    /// In percent.
+    #[serde(rename(deserialize = "relative_humidity_2m"))]
+    pub relative_humidity: Vec<f64>,
+    /// At 10 m, in km/h.
+    #[serde(rename(deserialize = "wind_speed_10m"))]
+    pub wind_speed: Vec<f64>,
+}
+
+impl ConditionsSeries {
+    /// The `hours` entries starting with the current hour (`now` in minutes
+    /// since the epoch, in the same timezone as `time`). Every series is cut to the same length, so a
+    /// short series never leaves the others misaligned.
+    pub fn next_hours(&self, now: i64, hours: usize) -> Self {
+        let len = [
+            self.time.len(),
+            self.precipitation.len(),
+            self.snowfall.len(),
+            self.cloud_cover.len(),
+            self.relative_humidity.len(),
+            self.wind_speed.len(),
+        ]
+        .into_iter()
+        .min()
+        .unwrap_or(0);
+        let current_hour = now - now.rem_euclid(60);
+        let start = self.time[..len]
+            .iter()
+            .position(|time| dates::timestamp_minutes(time).is_some_and(|t| t >= current_hour))
+            .unwrap_or(len);
+        let end = len.min(start + hours);
+
+        Self {
+            time: self.time[start..end].to_vec(),
+            precipitation: self.precipitation[start..end].to_vec(),
+            snowfall: self.snowfall[start..end].to_vec(),
+            cloud_cover: self.cloud_cover[start..end].to_vec(),
+            relative_humidity: self.relative_humidity[start..end].to_vec(),
+            wind_speed: self.wind_speed[start..end].to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Conditions {
+    pub city: String,
+    /// The timezone `time` is in.
+    pub timezone: String,
+    /// Unit of each series, keyed by field name.
+    pub units: HashMap<String, String>,
+    #[serde(flatten)]
+    pub series: ConditionsSeries,
+}
+
+impl Conditions {
+    /// `now` is in minutes since the epoch, UTC.
+    pub fn new(city: String, forecast: ConditionsForecast, now: i64, hours: usize) -> Self {
+        let mut units = forecast.hourly_units;
+        units.remove("time");
+        for (upstream, field) in [
+            ("relative_humidity_2m", "relative_humidity"),
+            ("wind_speed_10m", "wind_speed"),
+        ] {
+            if let Some(unit) = units.remove(upstream) {
+                units.insert(field.to_string(), unit);
+            }
+        }
+        // Hourly times are local to the forecast's timezone.
+        let now = now + forecast.utc_offset_seconds / 60;
+        Self {
+            city,
+            timezone: forecast.timezone,
+            units,
+            series: forecast.hourly.next_hours(now, hours),
+        }
+    }
+}
+
+impl Conditions {
+    /// Converts wind speed and precipitation from the units upstream
+    /// reported into the requested ones. Like Open-Meteo's own
+    /// `precipitation_unit`, inches also apply to snowfall, which otherwise
+    /// stays in cm. A series in a unit we don't know is left as it is.
+    pub fn convert_units(&mut self, wind: WindSpeedUnit, precipitation: PrecipitationUnit) {
+        let upstream_wind = self.unit("wind_speed").and_then(WindSpeedUnit::from_symbol);
+        if let Some(from) = upstream_wind {
+            for speed in &mut self.series.wind_speed {
+                *speed = wind.convert(*speed, from);
+            }
+            self.set_unit("wind_speed", wind.symbol());
+        }
+        let upstream_precipitation = self
+            .unit("precipitation")
+            .and_then(PrecipitationUnit::from_symbol);
+        if let Some(from) = upstream_precipitation {
+            for amount in &mut self.series.precipitation {
+                *amount = precipitation.convert(*amount, from);
+            }
+            self.set_unit("precipitation", precipitation.symbol());
+        }
+        if precipitation == PrecipitationUnit::Inch && self.unit("snowfall") == Some("cm") {
+            for amount in &mut self.series.snowfall {
+                *amount = units::mm_to_inches(*amount * 10.0);
+            }
+            self.set_unit("snowfall", precipitation.symbol());
+        }
+    }
+
+    fn unit(&self, field: &str) -> Option<&str> {
+        self.units.get(field).map(String::as_str)
+    }
+
+    fn set_unit(&mut self, field: &str, unit: &str) {
+        self.units.insert(field.to_string(), unit.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four hours of every series, except humidity, which is one short.
+    const SAMPLE: &str = r#"{
+        "timezone": "Europe/Berlin",
+        "utc_offset_seconds": 7200,
+        "hourly_units": {
+            "time": "iso8601",
+            "precipitation": "mm",
+            "snowfall": "cm",
+            "cloud_cover": "%",
+            "relative_humidity_2m": "%",
+            "wind_speed_10m": "km/h"
+        },
+        "hourly": {
+            "time": ["2024-05-01T12:00", "2024-05-01T13:00", "2024-05-01T14:00", "2024-05-01T15:00"],
+            "precipitation": [0.0, 0.4, 1.2, 0.1],
+            "snowfall": [0.0, 0.0, 0.0, 0.0],
+            "cloud_cover": [20.0, 80.0, 100.0, 60.0],
+            "relative_humidity_2m": [55.0, 70.0, 85.0],
+            "wind_speed_10m": [9.0, 18.0, 36.0, 27.0]
+        }
+    }"#;
+
+    fn minutes(time: &str) -> i64 {
+        dates::timestamp_minutes(time).unwrap()
+    }
+
+    #[test]
+    fn a_multi_variable_response_deserializes() {
+        let forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+
+        assert_eq!(forecast.timezone, "Europe/Berlin");
+        assert_eq!(forecast.hourly.time.len(), 4);
+        assert_eq!(forecast.hourly.precipitation[2], 1.2);
+        assert_eq!(forecast.hourly.relative_humidity, [55.0, 70.0, 85.0]);
+        assert_eq!(forecast.hourly_units["snowfall"], "cm");
+    }
+
+    #[test]
+    fn conditions_start_at_the_current_local_hour() {
+        let forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+        // 11:30 UTC is 13:30 in the forecast's timezone.
+        let now = minutes("2024-05-01T11:30");
+
+        let conditions = Conditions::new("Berlin".to_string(), forecast, now, 2);
+
+        assert_eq!(
+            conditions.series.time,
+            ["2024-05-01T13:00", "2024-05-01T14:00"]
+        );
+        assert_eq!(conditions.series.cloud_cover, [80.0, 100.0]);
+        assert_eq!(conditions.series.relative_humidity, [70.0, 85.0]);
+    }
+
+    #[test]
+    fn every_series_is_cut_to_the_shortest() {
+        let forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+
+        let series = forecast
+            .hourly
+            .next_hours(minutes("2024-05-01T12:00"), MAX_HOURS);
+
+        assert_eq!(series.time.len(), 3);
+        assert_eq!(series.precipitation.len(), 3);
+        assert_eq!(series.snowfall.len(), 3);
+        assert_eq!(series.cloud_cover.len(), 3);
+        assert_eq!(series.relative_humidity.len(), 3);
+        assert_eq!(series.wind_speed.len(), 3);
+    }
+
+    #[test]
+    fn units_are_keyed_like_the_returned_series() {
+        let forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+
+        let conditions = Conditions::new("Berlin".to_string(), forecast, 0, 1);
+
+        assert_eq!(conditions.units["relative_humidity"], "%");
+        assert!(!conditions.units.contains_key("relative_humidity_2m"));
+        assert!(!conditions.units.contains_key("time"));
+        assert_eq!(conditions.units["wind_speed"], "km/h");
+        assert!(!conditions.units.contains_key("wind_speed_10m"));
+    }
+
+    /// Whether every value in `actual` is within rounding error of `expected`.
+    fn close(actual: &[f64], expected: &[f64]) -> bool {
+        actual.len() == expected.len()
+            && actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-9)
+    }
+
+    #[test]
+    fn the_default_units_leave_the_series_as_they_came() {
+        let forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+        let mut conditions = Conditions::new(
+            "Berlin".to_string(),
+            forecast,
+            minutes("2024-05-01T10:00"),
+            3,
+        );
+
+        conditions.convert_units(WindSpeedUnit::default(), PrecipitationUnit::default());
+
+        assert_eq!(conditions.series.wind_speed, [9.0, 18.0, 36.0]);
+        assert_eq!(conditions.series.precipitation, [0.0, 0.4, 1.2]);
+        assert_eq!(conditions.units["wind_speed"], "km/h");
+        assert_eq!(conditions.units["precipitation"], "mm");
+        assert_eq!(conditions.units["snowfall"], "cm");
+    }
+
+    #[test]
+    fn wind_and_precipitation_are_converted_into_the_requested_units() {
+        let mut forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+        forecast.hourly.snowfall = vec![2.54, 0.0, 0.0, 0.0];
+        let mut conditions = Conditions::new(
+            "Berlin".to_string(),
+            forecast,
+            minutes("2024-05-01T10:00"),
+            3,
+        );
+
+        conditions.convert_units(WindSpeedUnit::Ms, PrecipitationUnit::Inch);
+
+        assert!(close(&conditions.series.wind_speed, &[2.5, 5.0, 10.0]));
+        assert!(close(
+            &conditions.series.precipitation,
+            &[0.0, 0.4 / 25.4, 1.2 / 25.4]
+        ));
+        assert!(close(&conditions.series.snowfall, &[1.0, 0.0, 0.0]));
+        assert_eq!(conditions.units["wind_speed"], "m/s");
+        assert_eq!(conditions.units["precipitation"], "inch");
+        assert_eq!(conditions.units["snowfall"], "inch");
+    }
+
+    #[test]
+    fn conversion_starts_from_the_unit_upstream_reported() {
+        let mut forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+        forecast
+            .hourly_units
+            .insert("wind_speed_10m".to_string(), "kn".to_string());
+        forecast
+            .hourly_units
+            .insert("precipitation".to_string(), "inch".to_string());
+        let mut conditions = Conditions::new(
+            "Berlin".to_string(),
+            forecast,
+            minutes("2024-05-01T10:00"),
+            2,
+        );
+
+        conditions.convert_units(WindSpeedUnit::Kmh, PrecipitationUnit::Mm);
+
+        assert!(close(&conditions.series.wind_speed, &[16.668, 33.336]));
+        assert!(close(&conditions.series.precipitation, &[0.0, 10.16]));
+        assert_eq!(conditions.units["wind_speed"], "km/h");
+        assert_eq!(conditions.units["precipitation"], "mm");
+    }
+
+    #[test]
+    fn a_series_in_an_unknown_unit_is_left_alone() {
+        let mut forecast: ConditionsForecast = serde_json::from_str(SAMPLE).unwrap();
+        forecast
+            .hourly_units
+            .insert("wind_speed_10m".to_string(), "bft".to_string());
+        let mut conditions = Conditions::new(
+            "Berlin".to_string(),
+            forecast,
+            minutes("2024-05-01T10:00"),
+            1,
+        );
+
+        conditions.convert_units(WindSpeedUnit::Mph, PrecipitationUnit::Mm);
+
+        assert_eq!(conditions.series.wind_speed, [9.0]);
+        assert_eq!(conditions.units["wind_speed"], "bft");
+    }
+
+    #[test]
+    fn hours_must_be_between_one_and_a_week() {
+        assert!(validate_hours(0).is_err());
+        assert_eq!(validate_hours(1), Ok(1));
+        assert_eq!(validate_hours(MAX_HOURS), Ok(MAX_HOURS));
+        assert!(validate_hours(MAX_HOURS + 1).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{error::ApiError, AppState};
+
+pub const DEADLINE_HEADER: &str = "x-request-deadline";
+
+/// Enforces the route's configured timeout and an optional per-request
+/// deadline, given in milliseconds via the `X-Request-Deadline` header and
+/// clamped to the configured maximum. Whichever is shorter applies.
+///
+/// When the deadline passes the handler future is dropped, which cancels any
+/// upstream calls still in flight, and the client gets a `504`.
+pub async fn enforce_deadline(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let route_timeout = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| state.config.route_timeouts_ms.get(path.as_str()))
+        .copied();
+    let requested = match req.headers().get(DEADLINE_HEADER) {
+        None => None,
+        Some(value) => match value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            Some(millis) => Some(millis.min(state.config.max_request_deadline_ms)),
+            None => {
+                return ApiError::BadRequest(
+                    "X-Request-Deadline must be a number of milliseconds".to_string(),
+                )
+                .into_response()
+            }
+        },
+    };
+    let Some(millis) = route_timeout.into_iter().chain(requested).min() else {
+        return next.run(req).await;
+    };
+
+    let deadline = Duration::from_millis(millis);
+    match tokio::time::timeout(deadline, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => ApiError::Timeout.into_response(),
+    }
+}
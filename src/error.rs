@@ -0,0 +1,137 @@
+use std::fmt;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+// The variant names follow the workshop's `ApiError` in `examples/`.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized,
+    Forbidden(String),
+    NotFound(String),
+    DatabaseError(String),
+    ExternalApiError(String),
+    Serialization(String),
+    Timeout,
+    Overloaded,
+    /// Several geocoding candidates fit equally well; holds their labels.
+    Ambiguous(Vec<String>),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::ExternalApiError(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Serialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Ambiguous(_) => StatusCode::MULTIPLE_CHOICES,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message) => f.write_str(message),
+            ApiError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            ApiError::ExternalApiError(e) => write!(f, "External API error: {}", e),
+            ApiError::Serialization(e) => write!(f, "Failed to serialize response: {}", e),
+            ApiError::Timeout => f.write_str("Request deadline exceeded"),
+            ApiError::Unauthorized => f.write_str("Unauthorized"),
+            ApiError::Overloaded => f.write_str("Server is busy, try again later"),
+            ApiError::Ambiguous(candidates) => write!(
+                f,
+                "Several places match equally well: {}",
+                candidates.join("; ")
+            ),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let candidates = match &self {
+            ApiError::Ambiguous(candidates) => candidates.clone(),
+            _ => Vec::new(),
+        };
+        (
+            self.status(),
+            Json(ErrorResponse {
+                error: self.to_string(),
+                candidates,
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Serializes `value` into a JSON response. Failures are logged and reported
+/// as [`ApiError::Serialization`] instead of axum's plain-text 500.
+pub fn json_response<T: Serialize>(value: &T) -> Result<Response, ApiError> {
+    let body = serde_json::to_vec(value).map_err(|e| {
+        tracing::error!("Failed to serialize response: {e}");
+        ApiError::Serialization(e.to_string())
+    })?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// The places to choose from, for [`ApiError::Ambiguous`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+
+    /// Fails to serialize, like a value no JSON can represent.
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("not representable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn serialization_failures_become_a_clean_500() {
+        let error = json_response(&Unserializable).unwrap_err();
+        let response = error.into_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["error"],
+            "Failed to serialize response: not representable"
+        );
+    }
+
+    #[test]
+    fn serializable_values_become_json_responses() {
+        let response = json_response(&serde_json::json!({ "ok": true })).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
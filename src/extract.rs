@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::ApiError;
+
+/// Like [`Query`], but rejects query strings that repeat a parameter.
+///
+/// With plain `Query`, `?city=London&city=Paris` silently picks one of the
+/// values. Guessing which city the client meant is worse than telling them,
+/// so repeats are answered with `400 Bad Request`.
+///
+/// Missing or malformed parameters are reported as an [`ApiError`], so the
+/// client gets the usual JSON error body instead of axum's plain text.
+pub struct StrictQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for StrictQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(name) = duplicate_parameter(parts.uri.query().unwrap_or_default()) {
+            return Err(ApiError::BadRequest(format!(
+                "Query parameter {name:?} was given more than once"
+            )));
+        }
+        let Query(value) = Query::try_from_uri(&parts.uri)
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+/// The first parameter name that appears twice in `query`, if any. Names
+/// are compared decoded, as `Query` sees them, so `%63ity` repeats `city`.
+fn duplicate_parameter(query: &str) -> Option<String> {
+    let mut seen = HashSet::new();
+    form_urlencoded::parse(query.as_bytes())
+        .map(|(name, _)| name.into_owned())
+        .find(|name| !seen.insert(name.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_parameters_are_found() {
+        assert_eq!(
+            duplicate_parameter("city=London&city=Paris").as_deref(),
+            Some("city")
+        );
+        assert_eq!(
+            duplicate_parameter("city=Rome&unit=c&flag&flag").as_deref(),
+            Some("flag")
+        );
+    }
+
+    #[test]
+    fn names_are_compared_after_decoding() {
+        assert_eq!(
+            duplicate_parameter("city=A&%63ity=B").as_deref(),
+            Some("city")
+        );
+        assert_eq!(
+            duplicate_parameter("start+hour=1&start%20hour=2").as_deref(),
+            Some("start hour")
+        );
+    }
+
+    #[test]
+    fn distinct_parameters_pass() {
+        assert_eq!(duplicate_parameter(""), None);
+        assert_eq!(duplicate_parameter("city=Rome&&cities=Rome"), None);
+    }
+}
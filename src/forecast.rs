@@ -0,0 +1,438 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{dates::Date, upstream::Api, variables::HourlyVariable, LatLong};
+
+/// How far back and ahead of today Open-Meteo's forecast API serves data.
+pub const MAX_PAST_DAYS: i64 = 92;
+pub const MAX_FORECAST_DAYS: i64 = 16;
+
+/// Elevations (in metres) accepted for the `elevation` override: from the
+/// Dead Sea shore up to just above Mount Everest.
+pub const ELEVATION_RANGE: std::ops::RangeInclusive<f64> = -450.0..=9000.0;
+
+/// Everything a client can ask for on top of the default hourly temperature.
+#[derive(Debug, Clone, Default)]
+pub struct ForecastOptions {
+    pub variables: Vec<HourlyVariable>,
+    pub date_range: Option<DateRange>,
+    /// Elevation in metres used for statistical downscaling instead of the
+    /// grid cell's own elevation.
+    pub elevation: Option<f64>,
+    pub cell_selection: Option<CellSelection>,
+    /// Temperature above ground in addition to the default at 2 m.
+    pub temperature_height: Option<TemperatureHeight>,
+    pub resolution: Resolution,
+    /// IANA timezone to return times in, taken from geocoding. Open-Meteo
+    /// answers in GMT without one.
+    pub timezone: Option<String>,
+}
+
+/// Tells clients which height `hourly.temperature_at_height` was measured at.
+#[derive(Serialize, Debug, Clone)]
+pub struct HeightInfo {
+    pub metres: u32,
+    pub variable: String,
+    pub label: String,
+}
+
+/// Heights above ground Open-Meteo reports temperature for, besides 2 m.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureHeight {
+    M80,
+    M120,
+    M180,
+}
+
+impl TemperatureHeight {
+    pub const ALL: [TemperatureHeight; 3] = [
+        TemperatureHeight::M80,
+        TemperatureHeight::M120,
+        TemperatureHeight::M180,
+    ];
+
+    pub fn metres(self) -> u32 {
+        match self {
+            TemperatureHeight::M80 => 80,
+            TemperatureHeight::M120 => 120,
+            TemperatureHeight::M180 => 180,
+        }
+    }
+
+    /// The hourly variable name, e.g. `temperature_80m`.
+    pub fn variable(self) -> String {
+        format!("temperature_{}m", self.metres())
+    }
+
+    pub fn describe(self) -> HeightInfo {
+        HeightInfo {
+            metres: self.metres(),
+            variable: self.variable(),
+            label: format!("{} m above ground", self.metres()),
+        }
+    }
+
+    pub fn from_metres(metres: u32) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|height| height.metres() == metres)
+            .ok_or_else(|| {
+                let supported: Vec<String> =
+                    Self::ALL.iter().map(|h| h.metres().to_string()).collect();
+                format!(
+                    "Unsupported height {metres}, expected one of: {} (metres)",
+                    supported.join(", ")
+                )
+            })
+    }
+}
+
+/// Which grid cell Open-Meteo picks for a coordinate.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CellSelection {
+    /// Prefer land cells with a similar elevation (the API's default).
+    Land,
+    /// Prefer sea cells.
+    Sea,
+    /// The nearest cell, whatever it is.
+    Nearest,
+}
+
+impl CellSelection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CellSelection::Land => "land",
+            CellSelection::Sea => "sea",
+            CellSelection::Nearest => "nearest",
+        }
+    }
+}
+
+/// Time step of the returned series.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resolution {
+    #[default]
+    #[serde(rename = "hourly")]
+    Hourly,
+    /// Only available for some variables, and in more detail for Central
+    /// Europe and North America than elsewhere.
+    #[serde(rename = "minutely_15")]
+    Minutely15,
+}
+
+impl Resolution {
+    /// The name of the block Open-Meteo requests and returns this series in.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Resolution::Hourly => "hourly",
+            Resolution::Minutely15 => "minutely_15",
+        }
+    }
+
+    /// Length of one step in minutes.
+    pub fn step_minutes(self) -> i64 {
+        match self {
+            Resolution::Hourly => 60,
+            Resolution::Minutely15 => 15,
+        }
+    }
+}
+
+/// Whether the step starting at `start` (minutes since the epoch) has
+/// already ended by `now`, so that its values are observations rather than
+/// a forecast. The step in progress still counts as forecast.
+pub fn is_observed(start: i64, step_minutes: i64, now: i64) -> bool {
+    start + step_minutes <= now
+}
+
+pub fn validate_elevation(elevation: f64) -> Result<f64, String> {
+    if ELEVATION_RANGE.contains(&elevation) {
+        Ok(elevation)
+    } else {
+        Err(format!(
+            "elevation must be between {} and {} metres",
+            ELEVATION_RANGE.start(),
+            ELEVATION_RANGE.end()
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DateRange {
+    pub start: Date,
+    pub end: Date,
+}
+
+impl DateRange {
+    /// Parses and validates a `start_date`/`end_date` pair. Both must be
+    /// given, in order, and inside the window the API can serve.
+    pub fn parse(start: &str, end: &str) -> Result<Self, String> {
+        let start: Date = start.parse()?;
+        let end: Date = end.parse()?;
+        if start > end {
+            return Err(format!("start_date {start} is after end_date {end}"));
+        }
+
+        let today = Date::today();
+        let earliest = today.add_days(-MAX_PAST_DAYS);
+        let latest = today.add_days(MAX_FORECAST_DAYS);
+        if start < earliest || end > latest {
+            return Err(format!(
+                "Dates must be between {earliest} and {latest}, got {start} to {end}"
+            ));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+pub fn forecast_url(lat_long: &LatLong, options: &ForecastOptions) -> Url {
+    multi_forecast_url(std::slice::from_ref(lat_long), options)
+}
+
+/// The cheapest forecast request that still tells us the location's timezone.
+pub fn timezone_url(lat_long: &LatLong) -> Url {
+    Api::Forecast.url([
+        ("latitude", lat_long.latitude.to_string()),
+        ("longitude", lat_long.longitude.to_string()),
+        ("timezone", "auto".to_string()),
+        ("forecast_days", "1".to_string()),
+    ])
+}
+
+/// Builds one forecast URL for several locations. Open-Meteo answers with an
+/// array holding one forecast per location, in the same order.
+pub fn multi_forecast_url(locations: &[LatLong], options: &ForecastOptions) -> Url {
+    let join = |coordinate: fn(&LatLong) -> f64| {
+        locations
+            .iter()
+            .map(|lat_long| coordinate(lat_long).to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let mut series = String::from("temperature_2m");
+    for variable in &options.variables {
+        series.push(',');
+        series.push_str(variable.as_str());
+    }
+    if let Some(height) = options.temperature_height {
+        series.push(',');
+        series.push_str(&height.variable());
+    }
+    let mut params = vec![
+        ("latitude", join(|lat_long| lat_long.latitude)),
+        ("longitude", join(|lat_long| lat_long.longitude)),
+        (options.resolution.as_str(), series),
+        ("current", "temperature_2m,weather_code".to_string()),
+    ];
+    if let Some(range) = options.date_range {
+        params.push(("start_date", range.start.to_string()));
+        params.push(("end_date", range.end.to_string()));
+    }
+    if let Some(elevation) = options.elevation {
+        params.push(("elevation", elevation.to_string()));
+    }
+    if let Some(cell_selection) = options.cell_selection {
+        params.push(("cell_selection", cell_selection.as_str().to_string()));
+    }
+    if let Some(timezone) = &options.timezone {
+        params.push(("timezone", timezone.clone()));
+    }
+    Api::Forecast.url(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn days_from_today(days: i64) -> String {
+        Date::today().add_days(days).to_string()
+    }
+
+    /// The query parameters of Berlin's forecast URL for `options`.
+    fn query(options: &ForecastOptions) -> HashMap<String, String> {
+        let lat_long = LatLong {
+            latitude: 52.52,
+            longitude: 13.41,
+        };
+        forecast_url(&lat_long, options)
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    #[test]
+    fn accepts_a_range_within_the_forecast_window() {
+        let range = DateRange::parse(&days_from_today(-2), &days_from_today(3)).unwrap();
+
+        assert_eq!(range.start, Date::today().add_days(-2));
+        assert_eq!(range.end, Date::today().add_days(3));
+    }
+
+    #[test]
+    fn accepts_the_edges_of_the_forecast_window() {
+        let earliest = days_from_today(-MAX_PAST_DAYS);
+        let latest = days_from_today(MAX_FORECAST_DAYS);
+
+        assert!(DateRange::parse(&earliest, &latest).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_range_ending_before_it_starts() {
+        let error = DateRange::parse(&days_from_today(2), &days_from_today(1)).unwrap_err();
+
+        assert!(error.contains("is after end_date"), "{error}");
+    }
+
+    #[test]
+    fn rejects_dates_outside_the_forecast_window() {
+        let too_early = days_from_today(-MAX_PAST_DAYS - 1);
+        let too_late = days_from_today(MAX_FORECAST_DAYS + 1);
+
+        assert!(DateRange::parse(&too_early, &days_from_today(0)).is_err());
+        assert!(DateRange::parse(&days_from_today(0), &too_late).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_dates() {
+        assert!(DateRange::parse("2024-5-1", "2024-05-02").is_err());
+        assert!(DateRange::parse("2024-02-30", "2024-03-01").is_err());
+    }
+
+    #[test]
+    fn the_range_is_sent_as_start_and_end_date() {
+        let options = ForecastOptions {
+            date_range: Some(DateRange {
+                start: "2024-05-01".parse().unwrap(),
+                end: "2024-05-03".parse().unwrap(),
+            }),
+            ..ForecastOptions::default()
+        };
+
+        let params = query(&options);
+
+        assert_eq!(params["start_date"], "2024-05-01");
+        assert_eq!(params["end_date"], "2024-05-03");
+    }
+
+    #[test]
+    fn the_resolution_picks_the_block_temperatures_are_requested_in() {
+        let hourly = query(&ForecastOptions::default());
+        let minutely = query(&ForecastOptions {
+            resolution: Resolution::Minutely15,
+            ..ForecastOptions::default()
+        });
+
+        assert_eq!(hourly["hourly"], "temperature_2m");
+        assert!(!hourly.contains_key("minutely_15"));
+        assert_eq!(minutely["minutely_15"], "temperature_2m");
+        assert!(!minutely.contains_key("hourly"));
+    }
+
+    #[test]
+    fn only_steps_that_have_ended_are_observed() {
+        let now = 10 * 60 + 30;
+
+        // 09:00 ended at 10:00; 10:00 is in progress; 11:00 hasn't begun.
+        assert!(is_observed(9 * 60, 60, now));
+        assert!(!is_observed(10 * 60, 60, now));
+        assert!(!is_observed(11 * 60, 60, now));
+        // A step ending exactly now is over.
+        assert!(is_observed(9 * 60 + 30, 60, now));
+        // 10:15 ended at 10:30 in 15-minute steps.
+        assert!(is_observed(10 * 60 + 15, 15, now));
+        assert!(!is_observed(10 * 60 + 30, 15, now));
+    }
+
+    #[test]
+    fn accepts_elevations_from_the_dead_sea_to_everest() {
+        assert_eq!(validate_elevation(-450.0), Ok(-450.0));
+        assert_eq!(validate_elevation(34.5), Ok(34.5));
+        assert_eq!(validate_elevation(9000.0), Ok(9000.0));
+    }
+
+    #[test]
+    fn rejects_elevations_outside_the_range() {
+        for elevation in [-451.0, 9000.5, f64::NAN] {
+            let error = validate_elevation(elevation).unwrap_err();
+
+            assert_eq!(error, "elevation must be between -450 and 9000 metres");
+        }
+    }
+
+    #[test]
+    fn elevation_and_cell_selection_are_sent_when_given() {
+        let options = ForecastOptions {
+            elevation: Some(120.0),
+            cell_selection: Some(CellSelection::Sea),
+            ..ForecastOptions::default()
+        };
+
+        let params = query(&options);
+
+        assert_eq!(params["elevation"], "120");
+        assert_eq!(params["cell_selection"], "sea");
+    }
+
+    #[test]
+    fn elevation_and_cell_selection_are_left_to_the_api_by_default() {
+        let params = query(&ForecastOptions::default());
+
+        assert!(!params.contains_key("elevation"));
+        assert!(!params.contains_key("cell_selection"));
+    }
+
+    #[test]
+    fn several_locations_share_one_url() {
+        let locations = [
+            LatLong {
+                latitude: 52.52,
+                longitude: 13.41,
+            },
+            LatLong {
+                latitude: 48.85,
+                longitude: 2.35,
+            },
+        ];
+
+        let params: HashMap<String, String> =
+            multi_forecast_url(&locations, &ForecastOptions::default())
+                .query_pairs()
+                .into_owned()
+                .collect();
+
+        assert_eq!(params["latitude"], "52.52,48.85");
+        assert_eq!(params["longitude"], "13.41,2.35");
+        assert_eq!(params["hourly"], "temperature_2m");
+    }
+
+    #[test]
+    fn supported_heights_map_to_their_variable() {
+        let height = TemperatureHeight::from_metres(120).unwrap();
+
+        assert_eq!(height.variable(), "temperature_120m");
+        assert_eq!(height.describe().label, "120 m above ground");
+    }
+
+    #[test]
+    fn unsupported_heights_list_the_supported_ones() {
+        let error = TemperatureHeight::from_metres(100).unwrap_err();
+
+        assert_eq!(
+            error,
+            "Unsupported height 100, expected one of: 80, 120, 180 (metres)"
+        );
+    }
+
+    #[test]
+    fn the_height_is_requested_alongside_temperature() {
+        let options = ForecastOptions {
+            temperature_height: Some(TemperatureHeight::M80),
+            ..ForecastOptions::default()
+        };
+
+        assert_eq!(query(&options)["hourly"], "temperature_2m,temperature_80m");
+    }
+}
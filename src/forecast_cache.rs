@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    cache::city_key, forecast::ForecastOptions, random::Sampler, summary::WeatherSummary,
+    units::TemperatureUnit, WeatherResponse,
+};
+
+/// Short-lived in-memory cache of forecasts, keyed by request shape.
+///
+/// Forecasts change hourly at most, so repeated requests for the same place
+/// within the TTL are served without calling Open-Meteo.
+///
+/// Entries read during the last `refresh_ahead` of their TTL are refreshed in
+/// the background, so hot entries are replaced before they expire. Expired
+/// entries are still served, and refreshed the same way, for a further
+/// `grace` period; only after that does a request wait for a fresh fetch.
+///
+/// Each entry's TTL is shortened by a random fraction of up to `jitter`, so
+/// that entries cached together (say, while warming up) don't all expire and
+/// get refetched at the same moment.
+pub struct ForecastCache {
+    ttl: Duration,
+    refresh_ahead: Duration,
+    grace: Duration,
+    jitter: f64,
+    sampler: Sampler,
+    entries: Mutex<HashMap<String, CachedForecast>>,
+    refreshing: Mutex<HashSet<String>>,
+    /// Aggregates computed from cached forecasts, under the same key and
+    /// expiring with the forecast they were computed from.
+    summaries: Mutex<HashMap<String, (SystemTime, WeatherSummary)>>,
+}
+
+/// Identifies one request shape: the same city asked for in another unit or
+/// with other variables is cached separately. `city` may also be a pair of
+/// rounded coordinates, shared by every city that rounds to it.
+pub fn cache_key(city: &str, unit: TemperatureUnit, options: &ForecastOptions) -> String {
+    let mut variables: Vec<&str> = options.variables.iter().map(|v| v.as_str()).collect();
+    variables.sort_unstable();
+    let date_range = options
+        .date_range
+        .map(|range| format!("{}..{}", range.start, range.end))
+        .unwrap_or_default();
+    let elevation = options
+        .elevation
+        .map(|elevation| elevation.to_string())
+        .unwrap_or_default();
+    let cell_selection = options
+        .cell_selection
+        .map(|cell_selection| cell_selection.as_str())
+        .unwrap_or_default();
+    let height = options
+        .temperature_height
+        .map(|height| height.variable())
+        .unwrap_or_default();
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}",
+        city_key(city),
+        unit.symbol(),
+        variables.join(","),
+        date_range,
+        elevation,
+        cell_selection,
+        height,
+        options.resolution.as_str(),
+        options.timezone.as_deref().unwrap_or_default()
+    )
+}
+
+#[derive(Clone)]
+pub struct CachedForecast {
+    /// When the forecast was fetched from Open-Meteo.
+    pub fetched_at: SystemTime,
+    /// When the entry stops being served: `fetched_at` plus a jittered TTL.
+    pub expires_at: SystemTime,
+    pub weather: WeatherResponse,
+}
+
+impl ForecastCache {
+    pub fn new(ttl: Duration, refresh_ahead: Duration, grace: Duration, jitter: f64) -> Self {
+        Self {
+            ttl,
+            refresh_ahead,
+            grace,
+            jitter: jitter.clamp(0.0, 1.0),
+            sampler: Sampler::default(),
+            entries: Mutex::new(HashMap::new()),
+            refreshing: Mutex::new(HashSet::new()),
+            summaries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn summary(&self, key: &str) -> Option<WeatherSummary> {
+        let now = SystemTime::now();
+        let mut summaries = self.summaries.lock().unwrap();
+        match summaries.get(key) {
+            Some((expires_at, summary)) if now < *expires_at => Some(summary.clone()),
+            Some(_) => {
+                summaries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remembers `summary` until `expires_at`, when the forecast it was
+    /// computed from expires.
+    pub fn insert_summary(&self, key: String, expires_at: SystemTime, summary: WeatherSummary) {
+        let now = SystemTime::now();
+        let mut summaries = self.summaries.lock().unwrap();
+        summaries.retain(|_, (expires_at, _)| now < *expires_at);
+        summaries.insert(key, (expires_at, summary));
+    }
+
+    /// Whether `cached` is close enough to expiry, or past it, to be
+    /// refreshed. Returns `true` to one caller only, until
+    /// [`Self::finish_refresh`] is called.
+    pub fn claim_refresh(&self, key: &str, cached: &CachedForecast) -> bool {
+        SystemTime::now() + self.refresh_ahead >= cached.expires_at
+            && self.refreshing.lock().unwrap().insert(key.to_string())
+    }
+
+    pub fn finish_refresh(&self, key: &str) {
+        self.refreshing.lock().unwrap().remove(key);
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedForecast> {
+        let now = SystemTime::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(cached) if now < cached.expires_at + self.grace => Some(cached.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Drops every cached forecast and summary, returning how many forecasts
+    /// there were.
+    pub fn clear(&self) -> usize {
+        self.summaries.lock().unwrap().clear();
+        let mut entries = self.entries.lock().unwrap();
+        let removed = entries.len();
+        entries.clear();
+        removed
+    }
+
+    pub fn insert(&self, key: String, weather: WeatherResponse) -> CachedForecast {
+        let now = SystemTime::now();
+        let ttl = self.ttl.mul_f64(1.0 - self.jitter * self.sampler.sample());
+        let cached = CachedForecast {
+            fetched_at: now,
+            expires_at: now + ttl,
+            weather,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now < entry.expires_at + self.grace);
+        entries.insert(key, cached.clone());
+        cached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use crate::variables::HourlyVariable;
+
+    fn with_variables(variables: Vec<HourlyVariable>) -> ForecastOptions {
+        ForecastOptions {
+            variables,
+            ..ForecastOptions::default()
+        }
+    }
+
+    #[test]
+    fn each_unit_gets_its_own_key() {
+        let options = ForecastOptions::default();
+
+        assert_ne!(
+            cache_key("Berlin", TemperatureUnit::Celsius, &options),
+            cache_key("Berlin", TemperatureUnit::Fahrenheit, &options)
+        );
+    }
+
+    #[test]
+    fn keys_ignore_variable_order_and_city_spelling() {
+        let one = with_variables(vec![
+            HourlyVariable::UvIndex,
+            HourlyVariable::RelativeHumidity,
+        ]);
+        let other = with_variables(vec![
+            HourlyVariable::RelativeHumidity,
+            HourlyVariable::UvIndex,
+        ]);
+
+        assert_eq!(
+            cache_key("Berlin", TemperatureUnit::Celsius, &one),
+            cache_key(" berlin ", TemperatureUnit::Celsius, &other)
+        );
+        assert_ne!(
+            cache_key("Berlin", TemperatureUnit::Celsius, &one),
+            cache_key(
+                "Berlin",
+                TemperatureUnit::Celsius,
+                &ForecastOptions::default()
+            )
+        );
+    }
+
+    fn berlin() -> WeatherResponse {
+        serde_json::from_str(&test_support::fixture("forecast_berlin.json")).unwrap()
+    }
+
+    /// The TTL of each of `count` entries inserted into `cache` at once.
+    fn ttls(cache: &ForecastCache, count: usize) -> Vec<Duration> {
+        (0..count)
+            .map(|i| {
+                let cached = cache.insert(format!("city-{i}"), berlin());
+                cached.expires_at.duration_since(cached.fetched_at).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jittered_entries_cached_together_expire_spread_out() {
+        let ttl = Duration::from_secs(600);
+        let cache = ForecastCache::new(ttl, Duration::ZERO, Duration::ZERO, 0.5);
+
+        let ttls = ttls(&cache, 200);
+
+        assert!(ttls.iter().all(|t| *t > ttl / 2 && *t <= ttl), "{ttls:?}");
+        let shortest = ttls.iter().min().unwrap();
+        let longest = ttls.iter().max().unwrap();
+        assert!(*longest - *shortest > Duration::from_secs(200), "{ttls:?}");
+        let distinct: HashSet<_> = ttls.iter().collect();
+        assert!(distinct.len() > 150, "{}", distinct.len());
+    }
+
+    #[test]
+    fn without_jitter_every_entry_gets_the_full_ttl() {
+        let ttl = Duration::from_secs(600);
+        let cache = ForecastCache::new(ttl, Duration::ZERO, Duration::ZERO, 0.0);
+
+        assert!(ttls(&cache, 20).iter().all(|t| *t == ttl));
+    }
+
+    #[test]
+    fn expired_entries_are_kept_through_the_grace_period_and_claimed_for_refresh() {
+        let cache =
+            ForecastCache::new(Duration::ZERO, Duration::ZERO, Duration::from_secs(60), 0.0);
+        cache.insert("berlin".to_string(), berlin());
+
+        let stale = cache.get("berlin").expect("served within the grace period");
+
+        assert!(stale.expires_at <= SystemTime::now());
+        assert!(cache.claim_refresh("berlin", &stale));
+        assert!(!cache.claim_refresh("berlin", &stale));
+    }
+
+    #[test]
+    fn expired_entries_past_the_grace_period_are_misses() {
+        let cache = ForecastCache::new(Duration::ZERO, Duration::ZERO, Duration::ZERO, 0.0);
+        cache.insert("berlin".to_string(), berlin());
+
+        assert!(cache.get("berlin").is_none());
+    }
+}
@@ -0,0 +1,35 @@
+//! A small browser frontend for `/weather`, compiled into the binary so the
+//! app stays a single self-contained file.
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+
+/// Files under `static/app`, served below `/app/`.
+static ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "index.html",
+        "text/html; charset=utf-8",
+        include_str!("../static/app/index.html"),
+    ),
+    (
+        "app.js",
+        "text/javascript; charset=utf-8",
+        include_str!("../static/app/app.js"),
+    ),
+];
+
+pub async fn index() -> Redirect {
+    Redirect::permanent("/app/index.html")
+}
+
+pub async fn asset(Path(path): Path<String>) -> Response {
+    match ASSETS.iter().find(|(name, _, _)| *name == path) {
+        Some((_, content_type, body)) => {
+            ([(header::CONTENT_TYPE, *content_type)], *body).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "Not found").into_response(),
+    }
+}
@@ -0,0 +1,295 @@
+use serde::Deserialize;
+
+use crate::{cache::city_key, LatLong};
+
+/// How many matches to ask the geocoding API for, so that a rejected match
+/// can be reported together with the alternatives.
+pub const CANDIDATES: usize = 5;
+/// How many matches to ask for when filtering by region, as the wanted
+/// place may rank well below same-named places elsewhere.
+pub const REGION_CANDIDATES: usize = 20;
+
+/// One match returned by the geocoding API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct GeoCandidate {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// GeoNames feature code, e.g. `PPLC` for a capital.
+    #[serde(default)]
+    pub feature_code: Option<String>,
+    #[serde(default)]
+    pub population: Option<u64>,
+    #[serde(default)]
+    pub country: Option<String>,
+    /// First-level administrative area: a state, province or region.
+    #[serde(default)]
+    pub admin1: Option<String>,
+    /// IANA timezone, e.g. `Europe/Berlin`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl GeoCandidate {
+    pub fn lat_long(&self) -> LatLong {
+        LatLong {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        }
+    }
+
+    /// `"Portland, Oregon, United States"`, leaving out whatever is unknown.
+    pub fn label(&self) -> String {
+        let mut label = self.name.clone();
+        for part in [&self.admin1, &self.country].into_iter().flatten() {
+            label.push_str(", ");
+            label.push_str(part);
+        }
+        label
+    }
+
+    /// Whether GeoNames classifies the candidate as a populated place (city,
+    /// town, village and so on) rather than, say, a sea or a mountain.
+    pub fn is_populated_place(&self) -> bool {
+        self.feature_code
+            .as_deref()
+            .is_some_and(|code| code.starts_with("PPL"))
+    }
+
+    /// Whether the candidate lies in `region`, matched against its first-level
+    /// administrative area or, failing that, its country.
+    pub fn in_region(&self, region: &str) -> bool {
+        let region = city_key(region);
+        [&self.admin1, &self.country]
+            .into_iter()
+            .flatten()
+            .any(|name| city_key(name) == region)
+    }
+}
+
+/// Splits `"Portland, Oregon"` into the place name and the region used to
+/// pick among same-named places. Names without a comma have no region.
+pub fn split_region(city: &str) -> (&str, Option<&str>) {
+    match city.rsplit_once(',') {
+        Some((name, region)) if !name.trim().is_empty() && !region.trim().is_empty() => {
+            (name.trim(), Some(region.trim()))
+        }
+        _ => (city, None),
+    }
+}
+
+/// Interprets `city` as `lat,lon` when it is exactly two in-range numbers,
+/// e.g. `48.85,2.35`. Anything else is left for the geocoder.
+pub fn parse_coordinates(city: &str) -> Option<LatLong> {
+    let (latitude, longitude) = city.split_once(',')?;
+    let latitude: f64 = latitude.trim().parse().ok()?;
+    let longitude: f64 = longitude.trim().parse().ok()?;
+    // `parse` accepts "inf" and "NaN", which the range checks reject.
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+    Some(LatLong {
+        latitude,
+        longitude,
+    })
+}
+
+/// The candidates sharing the highest [`confidence`] for `query`, in their
+/// original order. More than one means the query is ambiguous.
+pub fn top_tied<'a>(query: &str, candidates: &[&'a GeoCandidate]) -> Vec<&'a GeoCandidate> {
+    let scored: Vec<(f64, &GeoCandidate)> = candidates
+        .iter()
+        .map(|&candidate| (confidence(query, candidate), candidate))
+        .collect();
+    let Some(top) = scored.iter().map(|&(score, _)| score).reduce(f64::max) else {
+        return Vec::new();
+    };
+    scored
+        .into_iter()
+        .filter(|&(score, _)| (top - score).abs() < 1e-9)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Scores how likely `candidate` is what the user meant by `query`, from
+/// 0.0 to 1.0.
+///
+/// The name match dominates; the kind of place and its population break
+/// ties between, say, a capital and a village of the same name.
+pub fn confidence(query: &str, candidate: &GeoCandidate) -> f64 {
+    let query = city_key(query);
+    let name = city_key(&candidate.name);
+    let name_score = if name == query {
+        1.0
+    } else if name.starts_with(&query) || query.starts_with(&name) {
+        0.7
+    } else if name.contains(&query) || query.contains(&name) {
+        0.5
+    } else {
+        0.2
+    };
+
+    let feature_score = match candidate.feature_code.as_deref() {
+        Some("PPLC") => 1.0,
+        Some(code) if code.starts_with("PPLA") => 0.8,
+        Some(code) if code.starts_with("PPL") => 0.6,
+        _ => 0.3,
+    };
+
+    // A city of ten million scores 1.0, a hamlet of ten people about 0.14.
+    let population_score = match candidate.population {
+        Some(population) if population > 0 => ((population as f64).log10() / 7.0).min(1.0),
+        _ => 0.0,
+    };
+
+    0.6 * name_score + 0.2 * feature_score + 0.2 * population_score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, feature_code: &str, population: u64) -> GeoCandidate {
+        GeoCandidate {
+            name: name.to_string(),
+            latitude: 52.52,
+            longitude: 13.41,
+            feature_code: Some(feature_code.to_string()),
+            population: Some(population),
+            country: None,
+            admin1: None,
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn an_exact_match_on_a_capital_is_confident() {
+        let berlin = candidate("Berlin", "PPLC", 3_426_354);
+
+        let score = confidence("berlin", &berlin);
+
+        assert!(score > 0.95, "{score}");
+        assert!(score <= 1.0, "{score}");
+    }
+
+    #[test]
+    fn partial_names_and_small_places_score_lower() {
+        let berlin = candidate("Berlin", "PPLC", 3_426_354);
+        let village = candidate("Berlin", "PPL", 10);
+
+        assert!(confidence("Berl", &berlin) < confidence("Berlin", &berlin));
+        assert!(confidence("Erli", &berlin) < confidence("Berl", &berlin));
+        assert!(confidence("Paris", &berlin) < confidence("Erli", &berlin));
+        assert!(confidence("Berlin", &village) < confidence("Berlin", &berlin));
+    }
+
+    #[test]
+    fn candidates_without_details_still_get_a_score() {
+        let bare = GeoCandidate {
+            feature_code: None,
+            population: None,
+            ..candidate("Berlin", "", 0)
+        };
+
+        let score = confidence("Berlin", &bare);
+
+        assert!((score - 0.66).abs() < 1e-9, "{score}");
+    }
+
+    #[test]
+    fn only_candidates_tied_for_the_best_score_are_returned() {
+        let capital = candidate("Springfield", "PPLA", 100_000);
+        let twin = candidate("Springfield", "PPLA", 100_000);
+        let village = candidate("Springfield", "PPL", 500);
+
+        assert_eq!(
+            top_tied("Springfield", &[&capital, &twin, &village]).len(),
+            2
+        );
+        assert_eq!(top_tied("Springfield", &[&capital, &village]).len(), 1);
+        assert!(top_tied("Springfield", &[]).is_empty());
+    }
+
+    #[test]
+    fn coordinate_pairs_are_read_as_coordinates() {
+        let lat_long = parse_coordinates("48.85,2.35").unwrap();
+        assert_eq!((lat_long.latitude, lat_long.longitude), (48.85, 2.35));
+
+        let lat_long = parse_coordinates(" -33.87 , 151.21 ").unwrap();
+        assert_eq!((lat_long.latitude, lat_long.longitude), (-33.87, 151.21));
+    }
+
+    #[test]
+    fn anything_else_is_left_for_the_geocoder() {
+        for city in [
+            "Paris",
+            "Portland, Oregon",
+            "48.85",
+            "48.85,2.35,10",
+            "91,0",
+            "0,181",
+            "NaN,0",
+            "inf,0",
+        ] {
+            assert!(parse_coordinates(city).is_none(), "{city}");
+        }
+    }
+
+    #[test]
+    fn only_ppl_feature_codes_are_populated_places() {
+        for code in ["PPL", "PPLA", "PPLC", "PPLX"] {
+            assert!(
+                candidate("Somewhere", code, 0).is_populated_place(),
+                "{code}"
+            );
+        }
+        for code in ["SEA", "OCN", "MT", ""] {
+            assert!(
+                !candidate("Somewhere", code, 0).is_populated_place(),
+                "{code}"
+            );
+        }
+        let unclassified = GeoCandidate {
+            feature_code: None,
+            ..candidate("Somewhere", "PPL", 0)
+        };
+        assert!(!unclassified.is_populated_place());
+    }
+
+    #[test]
+    fn a_candidate_is_in_its_state_or_its_country_whatever_the_case() {
+        let portland = GeoCandidate {
+            admin1: Some("Oregon".to_string()),
+            country: Some("United States".to_string()),
+            ..candidate("Portland", "PPLA2", 652_503)
+        };
+
+        assert!(portland.in_region("Oregon"));
+        assert!(portland.in_region("  oregon "));
+        assert!(portland.in_region("United States"));
+        assert!(!portland.in_region("Maine"));
+        assert!(!candidate("Portland", "PPL", 0).in_region("Oregon"));
+    }
+
+    #[test]
+    fn labels_name_the_region_and_country_when_known() {
+        let portland = GeoCandidate {
+            admin1: Some("Maine".to_string()),
+            country: Some("United States".to_string()),
+            ..candidate("Portland", "PPLA2", 68_408)
+        };
+
+        assert_eq!(portland.label(), "Portland, Maine, United States");
+        assert_eq!(candidate("Portland", "PPL", 0).label(), "Portland");
+    }
+
+    #[test]
+    fn a_region_is_split_off_at_the_last_comma() {
+        assert_eq!(
+            split_region("Portland, Oregon"),
+            ("Portland", Some("Oregon"))
+        );
+        assert_eq!(split_region("Paris"), ("Paris", None));
+        assert_eq!(split_region("Paris,"), ("Paris,", None));
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+const HISTORY_TREE: &str = "history";
+
+/// One weather lookup, as recorded in the `history` tree.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub city: String,
+    pub requested_at: SystemTime,
+}
+
+/// Buffers request history in memory and writes it to sled in batches,
+/// either once `flush_size` entries have piled up or on a timer.
+///
+/// Anything still buffered at shutdown is written by a final [`flush`].
+///
+/// [`flush`]: HistoryWriter::flush
+pub struct HistoryWriter {
+    db: sled::Db,
+    flush_size: usize,
+    pending: Mutex<Vec<HistoryEntry>>,
+}
+
+impl HistoryWriter {
+    pub fn new(db: sled::Db, flush_size: usize) -> Self {
+        Self {
+            db,
+            flush_size: flush_size.max(1),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues a lookup for `city`, flushing if the buffer is full.
+    pub fn record(&self, city: &str) {
+        let full = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push(HistoryEntry {
+                city: city.to_string(),
+                requested_at: SystemTime::now(),
+            });
+            pending.len() >= self.flush_size
+        };
+        if full {
+            if let Err(e) = self.flush() {
+                tracing::warn!("Failed to flush request history: {e}");
+            }
+        }
+    }
+
+    /// Writes every buffered entry and returns how many were written. On
+    /// failure the entries stay buffered for the next attempt.
+    pub fn flush(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let entries = std::mem::take(&mut *self.pending.lock().unwrap());
+        if entries.is_empty() {
+            return Ok(0);
+        }
+        match self.write(&entries) {
+            Ok(()) => Ok(entries.len()),
+            Err(e) => {
+                let mut pending = self.pending.lock().unwrap();
+                let newer = std::mem::replace(&mut *pending, entries);
+                pending.extend(newer);
+                Err(e)
+            }
+        }
+    }
+
+    fn write(&self, entries: &[HistoryEntry]) -> Result<(), Box<dyn std::error::Error>> {
+        let tree = self.db.open_tree(HISTORY_TREE)?;
+        let mut batch = sled::Batch::default();
+        for entry in entries {
+            // Ids are monotonic, so big-endian keys keep the tree in insertion order.
+            let id = self.db.generate_id()?;
+            batch.insert(id.to_be_bytes().to_vec(), serde_json::to_vec(entry)?);
+        }
+        tree.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Flushes the buffer every `interval` until the process exits.
+    pub fn spawn_flusher(self: &Arc<Self>, interval: Duration) {
+        let writer = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = writer.flush() {
+                    tracing::warn!("Failed to flush request history: {e}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn writer(flush_size: usize) -> HistoryWriter {
+        HistoryWriter::new(
+            sled::Config::new().temporary(true).open().unwrap(),
+            flush_size,
+        )
+    }
+
+    /// The cities written so far, oldest first.
+    fn written(writer: &HistoryWriter) -> Vec<String> {
+        let tree = writer.db.open_tree(HISTORY_TREE).unwrap();
+        tree.iter()
+            .values()
+            .map(|value| {
+                let entry: HistoryEntry = serde_json::from_slice(&value.unwrap()).unwrap();
+                entry.city
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lookups_are_buffered_until_the_flush_size() {
+        let writer = writer(3);
+
+        writer.record("Berlin");
+        writer.record("Paris");
+        assert!(written(&writer).is_empty());
+
+        writer.record("Rome");
+        assert_eq!(written(&writer), ["Berlin", "Paris", "Rome"]);
+    }
+
+    #[test]
+    fn flush_writes_whatever_is_buffered() {
+        let writer = writer(50);
+        writer.record("Berlin");
+        writer.record("Paris");
+
+        assert_eq!(writer.flush().unwrap(), 2);
+        assert_eq!(writer.flush().unwrap(), 0);
+        writer.record("Rome");
+        assert_eq!(writer.flush().unwrap(), 1);
+
+        assert_eq!(written(&writer), ["Berlin", "Paris", "Rome"]);
+    }
+
+    #[test]
+    fn a_zero_flush_size_writes_every_lookup() {
+        let writer = writer(0);
+
+        writer.record("Berlin");
+
+        assert_eq!(written(&writer), ["Berlin"]);
+    }
+
+    #[tokio::test]
+    async fn the_flusher_writes_on_a_timer() {
+        let writer = Arc::new(writer(50));
+        writer.spawn_flusher(Duration::from_millis(20));
+
+        writer.record("Berlin");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(written(&writer), ["Berlin"]);
+    }
+}
@@ -0,0 +1,60 @@
+use serde::Serialize;
+
+/// A display hint for a WMO weather interpretation code.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icon {
+    /// Stable, machine-friendly name, e.g. `"rain"`.
+    pub name: &'static str,
+    pub emoji: &'static str,
+}
+
+const fn icon(name: &'static str, emoji: &'static str) -> Icon {
+    Icon { name, emoji }
+}
+
+/// Maps a WMO weather code (as returned in Open-Meteo's `weather_code`) to an
+/// icon. Codes not in the WMO table map to `"unknown"`.
+pub fn icon_for(code: u8) -> Icon {
+    match code {
+        0 => icon("clear", "☀️"),
+        1 => icon("mainly-clear", "🌤️"),
+        2 => icon("partly-cloudy", "⛅"),
+        3 => icon("overcast", "☁️"),
+        45 | 48 => icon("fog", "🌫️"),
+        51 | 53 | 55 => icon("drizzle", "🌦️"),
+        56 | 57 => icon("freezing-drizzle", "🌧️"),
+        61 | 63 | 65 => icon("rain", "🌧️"),
+        66 | 67 => icon("freezing-rain", "🌧️"),
+        71 | 73 | 75 => icon("snow", "🌨️"),
+        77 => icon("snow-grains", "❄️"),
+        80..=82 => icon("rain-showers", "🌦️"),
+        85 | 86 => icon("snow-showers", "🌨️"),
+        95 => icon("thunderstorm", "⛈️"),
+        96 | 99 => icon("thunderstorm-hail", "⛈️"),
+        _ => icon("unknown", "❓"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_code_in_a_group_shares_its_icon() {
+        for code in [61, 63, 65] {
+            assert_eq!(icon_for(code).name, "rain");
+        }
+        for code in 80..=82 {
+            assert_eq!(icon_for(code).name, "rain-showers");
+        }
+        assert_eq!(icon_for(0), icon("clear", "☀️"));
+        assert_eq!(icon_for(99).name, "thunderstorm-hail");
+    }
+
+    #[test]
+    fn codes_outside_the_wmo_table_are_unknown() {
+        for code in [4, 50, 100, 255] {
+            assert_eq!(icon_for(code).name, "unknown");
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! The parts of the app that the benchmarks and the workshop examples build
+//! on. The server itself is the `weather` binary in `main.rs`.
+
+use serde::{Deserialize, Serialize};
+
+pub mod cache;
+pub mod client_ip;
+pub mod error;
+#[cfg(feature = "redis")]
+pub mod redis_cache;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LatLong {
+    pub latitude: f64,
+    pub longitude: f64,
+}
@@ -0,0 +1,332 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use tracing::Instrument;
+
+use crate::{client_ip::client_ip, AppState};
+
+/// Longest response body logged by [`log_bodies`], in bytes.
+const MAX_LOGGED_BODY: usize = 2048;
+
+/// Decides which requests get an info-level log line.
+///
+/// The sampler is deterministic: with a rate of 0.25 exactly every fourth
+/// request is logged, which keeps the log volume predictable.
+#[derive(Debug)]
+pub struct LogSampler {
+    rate: f64,
+    seen: AtomicU64,
+}
+
+impl LogSampler {
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    pub fn sample(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.rate).floor() > (n * self.rate).floor()
+    }
+}
+
+/// Logs one line per request. Errors are always logged, successful requests
+/// only for the sampled fraction.
+///
+/// The request is handled inside a `request` span, so spans opened by the
+/// handler (such as `geocode` and `forecast`) nest under it.
+pub async fn log_requests(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    // The socket peer may be a reverse proxy; log the client behind it.
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| {
+            client_ip(peer.ip(), req.headers(), &state.config.trusted_proxies).to_string()
+        })
+        .unwrap_or_default();
+    let start = Instant::now();
+
+    let span = tracing::info_span!("request", %method, %uri);
+    let response = next.run(req).instrument(span).await;
+
+    let status = response.status();
+    let elapsed_ms = start.elapsed().as_millis();
+    if status.is_server_error() {
+        tracing::error!(%method, %uri, %status, elapsed_ms, %client, "request failed");
+    } else if status.is_client_error() {
+        tracing::warn!(%method, %uri, %status, elapsed_ms, %client, "request rejected");
+    } else if state.log_sampler.sample() {
+        tracing::info!(%method, %uri, %status, elapsed_ms, %client, "request served");
+    }
+
+    response
+}
+
+/// Logs request headers and query strings and response bodies at debug level,
+/// when enabled with `DEBUG_HTTP_BODIES=true`. Credentials are redacted and
+/// bodies are cut off after [`MAX_LOGGED_BODY`] bytes.
+pub async fn log_bodies(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if !state.config.debug_http_bodies {
+        return next.run(req).await;
+    }
+
+    let uri = req.uri().clone();
+    tracing::debug!(
+        %uri,
+        query = uri.query().unwrap_or_default(),
+        headers = ?redact(req.headers()),
+        "request"
+    );
+
+    let (parts, body) = next.run(req).await.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::debug!(%uri, "failed to read response body for logging: {e}");
+            return parts.status.into_response();
+        }
+    };
+    let logged = &bytes[..bytes.len().min(MAX_LOGGED_BODY)];
+    tracing::debug!(
+        %uri,
+        status = %parts.status,
+        body = %String::from_utf8_lossy(logged),
+        truncated = bytes.len() > MAX_LOGGED_BODY,
+        "response"
+    );
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Copies `headers`, replacing credentials with a placeholder.
+fn redact(headers: &HeaderMap) -> HeaderMap {
+    let mut headers = headers.clone();
+    for name in [header::AUTHORIZATION, header::COOKIE] {
+        if headers.contains_key(&name) {
+            headers.insert(name, HeaderValue::from_static("[redacted]"));
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::{self, MockUpstream, TestApp},
+    };
+
+    /// Everything any test has logged so far, including a line for every
+    /// closed span with its fields. The subscriber has to be the global one,
+    /// as the server handles requests on other tasks.
+    fn logs() -> String {
+        static LOGS: OnceLock<Arc<Mutex<Vec<u8>>>> = OnceLock::new();
+        let logs = LOGS.get_or_init(|| {
+            let logs = Arc::new(Mutex::new(Vec::new()));
+            let sink = Arc::clone(&logs);
+            tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::DEBUG)
+                .with_ansi(false)
+                .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+                .with_writer(move || LogWriter(Arc::clone(&sink)))
+                .init();
+            logs
+        });
+        String::from_utf8_lossy(&logs.lock().unwrap()).into_owned()
+    }
+
+    struct LogWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Serves Berlin with `timezone` in its forecast, to find the response
+    /// body in the logs by.
+    async fn serve_with_marker(debug_http_bodies: bool, timezone: &'static str) -> TestApp {
+        let mock = MockUpstream::start(move |request| match request.path.as_str() {
+            "/v1/forecast" => {
+                let mut forecast: serde_json::Value =
+                    serde_json::from_str(&test_support::fixture("forecast_berlin.json")).unwrap();
+                forecast["timezone"] = timezone.into();
+                (StatusCode::OK, forecast.to_string())
+            }
+            _ => test_support::open_meteo(request),
+        })
+        .await;
+        let mut config = Config::from_env();
+        config.debug_http_bodies = debug_http_bodies;
+        TestApp::serve(test_support::state(config, &mock)).await
+    }
+
+    #[tokio::test]
+    async fn bodies_are_logged_when_enabled_with_credentials_redacted() {
+        logs();
+        let app = serve_with_marker(true, "Test/LoggedBodies").await;
+
+        app.request(reqwest::Method::GET, "/weather?city=Berlin")
+            .header("Authorization", "Bearer hunter2")
+            .send()
+            .await
+            .unwrap();
+
+        let logs = logs();
+        assert!(logs.contains("Test/LoggedBodies"), "{logs}");
+        assert!(logs.contains("[redacted]"), "{logs}");
+        assert!(!logs.contains("hunter2"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn bodies_are_not_logged_by_default() {
+        logs();
+        let app = serve_with_marker(false, "Test/UnloggedBodies").await;
+
+        let response = app.get("/weather?city=Berlin").await;
+
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("Test/UnloggedBodies"));
+        assert!(!logs().contains("Test/UnloggedBodies"));
+    }
+
+    #[tokio::test]
+    async fn geocode_and_forecast_spans_nest_under_the_request_span() {
+        logs();
+        let mock = MockUpstream::start(|request| match request.path.as_str() {
+            "/v1/search" => (
+                StatusCode::OK,
+                test_support::fixture("geocoding_berlin.json"),
+            ),
+            _ => test_support::open_meteo(request),
+        })
+        .await;
+        let app = TestApp::serve(test_support::state(Config::from_env(), &mock)).await;
+
+        app.get("/weather?city=Spanville").await;
+
+        let logs = logs();
+        let request = "request{method=GET uri=/weather?city=Spanville}";
+        let closed = |span: &str| {
+            logs.lines()
+                .find(|line| {
+                    line.contains(&format!("{request}:{span}{{")) && line.contains("close")
+                })
+                .unwrap_or_else(|| panic!("no closed {span} span in {logs}"))
+                .to_string()
+        };
+        let geocode = closed("geocode");
+        assert!(geocode.contains("city=Spanville"), "{geocode}");
+        assert!(geocode.contains("status=\"ok\""), "{geocode}");
+        assert!(geocode.contains("latency_ms="), "{geocode}");
+        let forecast = closed("forecast");
+        assert!(forecast.contains("latitude=52.52437"), "{forecast}");
+        assert!(forecast.contains("status=\"ok\""), "{forecast}");
+        assert!(forecast.contains("latency_ms="), "{forecast}");
+    }
+
+    #[tokio::test]
+    async fn a_rate_of_zero_still_logs_failed_requests() {
+        logs();
+        let mock = MockUpstream::start(|request| match request.param("name") {
+            "SampledOut" => (
+                StatusCode::OK,
+                test_support::fixture("geocoding_berlin.json"),
+            ),
+            "FailedUpstream" => (StatusCode::SERVICE_UNAVAILABLE, String::new()),
+            _ => test_support::open_meteo(request),
+        })
+        .await;
+        let mut config = Config::from_env();
+        config.log_sample_rate = 0.0;
+        let app = TestApp::serve(test_support::state(config, &mock)).await;
+
+        let served = app.get("/weather?city=SampledOut").await;
+        let failed = app.get("/weather?city=FailedUpstream").await;
+
+        assert!(served.status().is_success());
+        assert!(failed.status().is_server_error());
+        let logs = logs();
+        let logged = |uri: &str, message: &str| {
+            logs.lines()
+                .any(|line| line.contains(&format!("uri={uri} ")) && line.contains(message))
+        };
+        assert!(
+            !logged("/weather?city=SampledOut", "request served"),
+            "{logs}"
+        );
+        assert!(
+            logged("/weather?city=FailedUpstream", "request failed"),
+            "{logs}"
+        );
+    }
+
+    #[test]
+    fn credentials_are_redacted_and_other_headers_kept() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic Zm9v"),
+        );
+        headers.insert(header::COOKIE, HeaderValue::from_static("session=1"));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+
+        let redacted = redact(&headers);
+
+        assert_eq!(redacted[header::AUTHORIZATION], "[redacted]");
+        assert_eq!(redacted[header::COOKIE], "[redacted]");
+        assert_eq!(redacted[header::ACCEPT], "application/json");
+    }
+
+    fn sampled(rate: f64, requests: usize) -> Vec<bool> {
+        let sampler = LogSampler::new(rate);
+        (0..requests).map(|_| sampler.sample()).collect()
+    }
+
+    #[test]
+    fn a_rate_of_one_logs_every_request() {
+        assert_eq!(sampled(1.0, 4), [true; 4]);
+    }
+
+    #[test]
+    fn a_rate_of_zero_logs_nothing() {
+        assert_eq!(sampled(0.0, 4), [false; 4]);
+    }
+
+    #[test]
+    fn a_quarter_logs_exactly_every_fourth_request() {
+        let expected: Vec<bool> = (1..=12).map(|n| n % 4 == 0).collect();
+        assert_eq!(sampled(0.25, 12), expected);
+    }
+
+    #[test]
+    fn rates_outside_the_unit_interval_are_clamped() {
+        assert_eq!(sampled(7.0, 4), [true; 4]);
+        assert_eq!(sampled(-1.0, 4), [false; 4]);
+    }
+}
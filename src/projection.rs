@@ -0,0 +1,92 @@
+use serde::{ser::Error as _, ser::SerializeMap, Serialize, Serializer};
+use serde_json::Value;
+
+/// Serializes only the listed top-level fields of a value that serializes to
+/// a JSON object. Other values are serialized unchanged.
+pub struct Projection<'a, T> {
+    value: &'a T,
+    fields: &'a [String],
+}
+
+impl<'a, T> Projection<'a, T> {
+    pub fn new(value: &'a T, fields: &'a [String]) -> Self {
+        Self { value, fields }
+    }
+}
+
+impl<T: Serialize> Serialize for Projection<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = serde_json::to_value(self.value).map_err(S::Error::custom)?;
+        let Value::Object(object) = value else {
+            return value.serialize(serializer);
+        };
+        let mut map = serializer.serialize_map(None)?;
+        for (key, value) in &object {
+            if self.fields.iter().any(|field| field == key) {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Parses a comma-separated field list, rejecting names not in `allowed`.
+pub fn parse_fields(list: &str, allowed: &[&str]) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if !allowed.contains(&name) {
+            return Err(format!(
+                "Unknown field {name:?}, expected one of: {}",
+                allowed.join(", ")
+            ));
+        }
+        if !fields.iter().any(|field| field == name) {
+            fields.push(name.to_string());
+        }
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn only_the_listed_fields_are_serialized() {
+        let value = json!({ "timezone": "GMT", "hourly": [1], "current": {} });
+        let fields = vec!["timezone".to_string(), "current".to_string()];
+
+        let projected = serde_json::to_value(Projection::new(&value, &fields)).unwrap();
+
+        assert_eq!(projected, json!({ "timezone": "GMT", "current": {} }));
+    }
+
+    #[test]
+    fn non_objects_are_serialized_unchanged() {
+        let fields = vec!["timezone".to_string()];
+
+        let projected = serde_json::to_value(Projection::new(&json!([1, 2]), &fields)).unwrap();
+
+        assert_eq!(projected, json!([1, 2]));
+    }
+
+    #[test]
+    fn field_lists_are_trimmed_and_deduplicated() {
+        let fields = parse_fields(" hourly,,timezone , hourly", &["hourly", "timezone"]);
+
+        assert_eq!(fields.unwrap(), ["hourly", "timezone"]);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let error = parse_fields("hourly,nope", &["hourly"]).unwrap_err();
+
+        assert!(error.starts_with("Unknown field \"nope\""), "{error}");
+    }
+}
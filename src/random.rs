@@ -0,0 +1,21 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Cheap, uniformly distributed numbers for jitter and fault injection.
+/// Not suitable for anything security-related.
+#[derive(Debug, Default)]
+pub struct Sampler {
+    random: RandomState,
+    calls: AtomicU64,
+}
+
+impl Sampler {
+    /// The next sample, in `[0, 1)`.
+    pub fn sample(&self) -> f64 {
+        // Hashing a counter with a randomly keyed hasher is a cheap source of
+        // uniformly distributed numbers, good enough for this purpose.
+        let n = self.calls.fetch_add(1, Ordering::Relaxed);
+        (self.random.hash_one(n) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
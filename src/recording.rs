@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Whether upstream traffic is written to or served from disk, for
+/// reproducing a reported issue without depending on the live APIs.
+#[derive(Debug, Clone, Default)]
+pub enum Recording {
+    #[default]
+    Off,
+    /// Save every upstream response in this directory.
+    Record(PathBuf),
+    /// Answer upstream requests from the responses saved in this directory
+    /// and never touch the network.
+    Replay(PathBuf),
+}
+
+/// One recorded upstream exchange, stored as `<key>.json`.
+#[derive(Serialize, Deserialize)]
+pub struct Exchange {
+    /// The normalized request, kept for whoever reads the recording.
+    pub request: String,
+    pub status: u16,
+    pub body: String,
+}
+
+/// The request with its query parameters sorted, so that recordings don't
+/// depend on the order the parameters were added in.
+pub fn normalize(url: &Url) -> String {
+    let mut params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    params.sort();
+    let mut normalized = url.clone();
+    normalized.set_fragment(None);
+    normalized.set_query(None);
+    if !params.is_empty() {
+        normalized.query_pairs_mut().extend_pairs(params);
+    }
+    normalized.into()
+}
+
+/// File name for a normalized request: a 64-bit FNV-1a hash, which (unlike
+/// `DefaultHasher`) is the same across builds, so recordings stay usable.
+fn file_name(request: &str) -> String {
+    let hash = request
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}.json")
+}
+
+/// Saves the response to `url` in `dir`, replacing any earlier recording.
+pub fn save(dir: &Path, url: &Url, status: u16, body: &[u8]) -> std::io::Result<()> {
+    let request = normalize(url);
+    let path = dir.join(file_name(&request));
+    let exchange = Exchange {
+        request,
+        status,
+        body: String::from_utf8_lossy(body).into_owned(),
+    };
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(path, serde_json::to_vec_pretty(&exchange)?)
+}
+
+/// Loads the recorded response to `url` from `dir`.
+pub fn load(dir: &Path, url: &Url) -> Result<Exchange, String> {
+    let request = normalize(url);
+    let path = dir.join(file_name(&request));
+    let bytes = std::fs::read(&path)
+        .map_err(|e| format!("no recording of {request} ({}): {e}", path.display()))?;
+    let exchange: Exchange = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("unreadable recording {}: {e}", path.display()))?;
+    if exchange.request != request {
+        return Err(format!(
+            "recording {} is for {}, not {request}",
+            path.display(),
+            exchange.request
+        ));
+    }
+    Ok(exchange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn normalizing_sorts_the_parameters_and_drops_the_fragment() {
+        let url =
+            Url::parse("https://api.example.com/v1/forecast?longitude=13.4&latitude=52.5#top")
+                .unwrap();
+
+        assert_eq!(
+            normalize(&url),
+            "https://api.example.com/v1/forecast?latitude=52.5&longitude=13.4"
+        );
+    }
+
+    #[test]
+    fn a_saved_exchange_loads_back_under_any_parameter_order() {
+        let dir = test_support::temp_dir("save-load");
+        let url = Url::parse("https://api.example.com/v1/search?name=Berlin&count=5").unwrap();
+        let reordered =
+            Url::parse("https://api.example.com/v1/search?count=5&name=Berlin").unwrap();
+
+        save(&dir, &url, 200, br#"{"results":[]}"#).unwrap();
+        let exchange = load(&dir, &reordered).unwrap();
+
+        assert_eq!(exchange.status, 200);
+        assert_eq!(exchange.body, r#"{"results":[]}"#);
+        assert_eq!(exchange.request, normalize(&url));
+    }
+
+    #[test]
+    fn loading_an_unrecorded_request_names_it() {
+        let dir = test_support::temp_dir("load-missing");
+        let url = Url::parse("https://api.example.com/v1/search?name=Paris").unwrap();
+
+        let error = load(&dir, &url).err().unwrap();
+
+        assert!(
+            error.starts_with("no recording of https://api.example.com/v1/search?name=Paris"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use axum::async_trait;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+use crate::{
+    cache::{db_error, CacheBackend, CacheEntry},
+    error::ApiError,
+};
+
+/// Namespaces our keys when the Redis instance is shared with other services.
+const KEY_PREFIX: &str = "weather:geo:";
+
+/// Geocoding cache shared between instances through Redis.
+pub struct RedisCache {
+    connection: MultiplexedConnection,
+}
+
+impl RedisCache {
+    pub async fn connect(url: &str) -> redis::RedisResult<Self> {
+        let client = redis::Client::open(url)?;
+        let connection = client.get_multiplexed_async_connection().await?;
+        Ok(Self { connection })
+    }
+}
+
+fn key(city: &str) -> String {
+    format!("{KEY_PREFIX}{city}")
+}
+
+impl RedisCache {
+    /// Every key of ours, found with `SCAN` so Redis isn't blocked.
+    async fn keys(&self) -> Result<Vec<String>, ApiError> {
+        let mut connection = self.connection.clone();
+        let mut iter = connection
+            .scan_match::<_, String>(format!("{KEY_PREFIX}*"))
+            .await
+            .map_err(db_error)?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
+
+#[async_trait]
+impl CacheBackend for RedisCache {
+    async fn get(&self, city: &str) -> Result<Option<CacheEntry>, ApiError> {
+        let mut connection = self.connection.clone();
+        let bytes: Option<Vec<u8>> = connection.get(key(city)).await.map_err(db_error)?;
+        bytes
+            .map(|bytes| serde_json::from_slice(&bytes).map_err(db_error))
+            .transpose()
+    }
+
+    async fn set(&self, city: &str, entry: CacheEntry) -> Result<(), ApiError> {
+        let bytes = serde_json::to_vec(&entry).map_err(db_error)?;
+        let mut connection = self.connection.clone();
+        connection
+            .set::<_, _, ()>(key(city), bytes)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn remove(&self, city: &str) -> Result<(), ApiError> {
+        let mut connection = self.connection.clone();
+        connection.del::<_, ()>(key(city)).await.map_err(db_error)
+    }
+
+    async fn clear(&self) -> Result<usize, ApiError> {
+        let keys = self.keys().await?;
+        if !keys.is_empty() {
+            let mut connection = self.connection.clone();
+            connection.del::<_, ()>(&keys).await.map_err(db_error)?;
+        }
+        Ok(keys.len())
+    }
+
+    async fn entries(&self) -> Result<Vec<(String, CacheEntry)>, ApiError> {
+        let mut entries = Vec::new();
+        for key in self.keys().await? {
+            let city = key.strip_prefix(KEY_PREFIX).unwrap_or(&key).to_string();
+            // Keys may expire or be removed between the scan and the read.
+            if let Some(entry) = self.get(&city).await? {
+                entries.push((city, entry));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Runs against the Redis server at `REDIS_URL`, and is skipped without one.
+/// Note that it clears every key of ours on that server.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LatLong;
+
+    #[tokio::test]
+    async fn stores_lists_and_removes_entries() {
+        let Ok(url) = std::env::var("REDIS_URL") else {
+            eprintln!("REDIS_URL is not set, skipping");
+            return;
+        };
+        let cache = RedisCache::connect(&url).await.unwrap();
+        cache.clear().await.unwrap();
+        let entry = CacheEntry::new(LatLong {
+            latitude: 52.52,
+            longitude: 13.41,
+        });
+
+        cache.set("Berlin", entry.clone()).await.unwrap();
+        cache.set("Paris", entry).await.unwrap();
+        let stored = cache.get("Berlin").await.unwrap().unwrap();
+        assert_eq!(stored.lat_long.latitude, 52.52);
+        let mut cities: Vec<String> = cache
+            .entries()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(city, _)| city)
+            .collect();
+        cities.sort();
+        assert_eq!(cities, ["Berlin", "Paris"]);
+
+        cache.remove("Berlin").await.unwrap();
+        assert!(cache.get("Berlin").await.unwrap().is_none());
+        assert_eq!(cache.clear().await.unwrap(), 1);
+    }
+}
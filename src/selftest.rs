@@ -0,0 +1,117 @@
+//! Startup self-test, enabled with `--selftest` or `SELFTEST=true`.
+//!
+//! Checks the dependencies a deployment needs, prints one line per check and
+//! exits instead of serving, so deploy pipelines can gate on the exit code.
+
+use crate::upstream::Upstream;
+
+/// Geocoded during the self-test; any city the provider knows will do.
+const KNOWN_CITY: &str = "Berlin";
+const PROBE_KEY: &[u8] = b"__selftest__";
+
+/// Runs every check and reports whether all of them passed.
+pub async fn run(db: &sled::Db, upstream: &Upstream) -> bool {
+    let checks = [
+        ("database", check_database(db)),
+        ("geocoding", check_geocoding(upstream).await),
+    ];
+    let mut passed = true;
+    for (name, result) in checks {
+        match result {
+            Ok(detail) => println!("ok    {name}: {detail}"),
+            Err(e) => {
+                println!("FAIL  {name}: {e}");
+                passed = false;
+            }
+        }
+    }
+    passed
+}
+
+/// Writes, reads back and removes a probe key.
+fn check_database(db: &sled::Db) -> Result<String, String> {
+    let result = probe(db);
+    let _ = db.remove(PROBE_KEY);
+    match result {
+        Ok(Some(value)) if &*value == b"ok" => Ok("read back a probe value".to_string()),
+        Ok(_) => Err("probe value was not read back".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn probe(db: &sled::Db) -> sled::Result<Option<sled::IVec>> {
+    db.insert(PROBE_KEY, &b"ok"[..])?;
+    db.flush()?;
+    db.get(PROBE_KEY)
+}
+
+async fn check_geocoding(upstream: &Upstream) -> Result<String, String> {
+    let lat_long = crate::fetch_lat_long(upstream, KNOWN_CITY, 0.0, false, false)
+        .await
+        .map(|entry| entry.lat_long)
+        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "{KNOWN_CITY} resolved to {}, {}",
+        lat_long.latitude, lat_long.longitude
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::test_support::{self, MockUpstream};
+    use crate::Config;
+
+    #[tokio::test]
+    async fn the_self_test_passes_against_a_working_provider() {
+        let mock = MockUpstream::open_meteo().await;
+        let state = test_support::state(Config::from_env(), &mock);
+
+        let passed = run(&state.db, &state.upstream).await;
+
+        assert!(passed);
+        let searched = mock.requests("/v1/search");
+        assert_eq!(searched.len(), 1);
+        assert_eq!(searched[0].param("name"), KNOWN_CITY);
+        assert_eq!(state.db.get(PROBE_KEY).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn geocoding_fails_when_the_provider_does_not_know_the_city() {
+        let mock = MockUpstream::start(|_| {
+            (
+                StatusCode::OK,
+                test_support::fixture("geocoding_no_results.json"),
+            )
+        })
+        .await;
+        let state = test_support::state(Config::from_env(), &mock);
+
+        let result = check_geocoding(&state.upstream).await;
+
+        assert!(result.is_err());
+        assert!(!run(&state.db, &state.upstream).await);
+    }
+
+    #[tokio::test]
+    async fn geocoding_fails_when_the_provider_is_down() {
+        let mock = MockUpstream::start(|_| (StatusCode::SERVICE_UNAVAILABLE, String::new())).await;
+        let state = test_support::state(Config::from_env(), &mock);
+
+        let passed = run(&state.db, &state.upstream).await;
+
+        assert!(!passed);
+    }
+
+    #[test]
+    fn the_database_check_reads_back_and_removes_its_probe() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+
+        let result = check_database(&db);
+
+        assert_eq!(result, Ok("read back a probe value".to_string()));
+        assert_eq!(db.get(PROBE_KEY).unwrap(), None);
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use tokio::task::JoinHandle;
+
+use crate::AppState;
+
+/// Resolves on Ctrl+C, or on SIGTERM where that exists.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Gives `server` up to `timeout` to finish the requests it is still
+/// serving, then aborts it, logging whatever was cut off. Returns whether it
+/// finished in time.
+pub async fn drain<T>(server: &mut JoinHandle<T>, timeout: Duration, in_flight: &InFlight) -> bool {
+    if tokio::time::timeout(timeout, &mut *server).await.is_ok() {
+        return true;
+    }
+    for request in in_flight.describe() {
+        tracing::warn!("Force-closing connection still serving {request}");
+    }
+    server.abort();
+    false
+}
+
+/// Requests currently being handled, so that shutdown can report which ones
+/// it had to abandon once the drain timeout passed.
+#[derive(Default)]
+pub struct InFlight {
+    next_id: AtomicU64,
+    requests: Mutex<HashMap<u64, (String, Instant)>>,
+}
+
+impl InFlight {
+    fn start(self: &Arc<Self>, description: String) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.requests
+            .lock()
+            .unwrap()
+            .insert(id, (description, Instant::now()));
+        InFlightGuard {
+            in_flight: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// Describes every request still running, e.g. `GET /weather?city=Oslo (12s)`.
+    pub fn describe(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(description, started)| {
+                format!("{description} ({}s)", started.elapsed().as_secs())
+            })
+            .collect()
+    }
+}
+
+struct InFlightGuard {
+    in_flight: Arc<InFlight>,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.requests.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub async fn track_in_flight(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let _guard = state
+        .in_flight
+        .start(format!("{} {}", req.method(), req.uri()));
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_tracked_until_they_finish() {
+        let in_flight = Arc::new(InFlight::default());
+
+        let guard = in_flight.start("GET /weather?city=Oslo".to_string());
+        assert_eq!(in_flight.describe(), ["GET /weather?city=Oslo (0s)"]);
+
+        drop(guard);
+        assert!(in_flight.describe().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_server_finishing_within_the_timeout_is_drained() {
+        let mut server = tokio::spawn(tokio::time::sleep(Duration::from_millis(10)));
+
+        let drained = drain(&mut server, Duration::from_secs(5), &InFlight::default()).await;
+
+        assert!(drained);
+    }
+
+    #[tokio::test]
+    async fn a_server_still_busy_after_the_timeout_is_aborted() {
+        let mut server = tokio::spawn(std::future::pending::<()>());
+
+        let drained = drain(&mut server, Duration::from_millis(10), &InFlight::default()).await;
+
+        assert!(!drained);
+        assert!(server.await.unwrap_err().is_cancelled());
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{upstream::Api, LatLong};
+
+/// Days covered by `/weather/sparkline`.
+const DAYS: usize = 7;
+const WIDTH: f64 = 120.0;
+const HEIGHT: f64 = 30.0;
+/// Keeps the line's stroke inside the image at the extremes.
+const PADDING: f64 = 2.0;
+
+/// Days start at local midnight in `timezone`.
+pub fn sparkline_url(lat_long: &LatLong, timezone: &str) -> Url {
+    Api::Forecast.url([
+        ("latitude", lat_long.latitude.to_string()),
+        ("longitude", lat_long.longitude.to_string()),
+        ("daily", "temperature_2m_max".to_string()),
+        ("timezone", timezone.to_string()),
+        ("forecast_days", DAYS.to_string()),
+    ])
+}
+
+/// The forecast response for [`sparkline_url`].
+#[derive(Deserialize, Debug)]
+pub struct DailyForecast {
+    pub daily: DailyMax,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DailyMax {
+    pub temperature_2m_max: Vec<Option<f64>>,
+}
+
+/// Draws `values` as a line chart, one point per value, scaled to fill the
+/// image vertically. Missing values are left out of the line. The range,
+/// in `unit`, goes into the title for screen readers and tooltips.
+pub fn render(values: &[Option<f64>], unit: &str) -> String {
+    let present = values.iter().flatten().copied();
+    let min = present.clone().fold(f64::INFINITY, f64::min);
+    let max = present.fold(f64::NEG_INFINITY, f64::max);
+    let step = (WIDTH - 2.0 * PADDING) / values.len().saturating_sub(1).max(1) as f64;
+
+    let mut points = String::new();
+    for (i, value) in values.iter().enumerate() {
+        let Some(value) = value else {
+            continue;
+        };
+        // A flat week is drawn through the middle.
+        let y = if max > min {
+            PADDING + (max - value) / (max - min) * (HEIGHT - 2.0 * PADDING)
+        } else {
+            HEIGHT / 2.0
+        };
+        if !points.is_empty() {
+            points.push(' ');
+        }
+        let _ = write!(points, "{:.1},{:.1}", PADDING + i as f64 * step, y);
+    }
+
+    let title = if min <= max {
+        format!("Daily highs from {min:.1} to {max:.1} {unit}")
+    } else {
+        "No daily highs available".to_string()
+    };
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}"><title>{title}</title><polyline points="{points}" fill="none" stroke="currentColor" stroke-width="1.5"/></svg>"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `x,y` pairs of the polyline in `svg`.
+    fn points(svg: &str) -> Vec<(f64, f64)> {
+        let start = svg.find("points=\"").unwrap() + "points=\"".len();
+        let end = start + svg[start..].find('"').unwrap();
+        svg[start..end]
+            .split_whitespace()
+            .map(|point| {
+                let (x, y) = point.split_once(',').unwrap();
+                (x.parse().unwrap(), y.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_day_becomes_a_point_spanning_the_image() {
+        let highs = [12.0, 14.5, 13.0, 18.0, 16.5, 15.0, 11.0].map(Some);
+
+        let svg = render(&highs, "°C");
+
+        let points = points(&svg);
+        assert_eq!(points.len(), 7);
+        assert_eq!(points[0].0, PADDING);
+        assert_eq!(points[6].0, WIDTH - PADDING);
+        // The warmest day is drawn at the top, the coldest at the bottom.
+        assert_eq!(points[3].1, PADDING);
+        assert_eq!(points[6].1, HEIGHT - PADDING);
+        assert!(svg.contains("<title>Daily highs from 11.0 to 18.0 °C</title>"));
+    }
+
+    #[test]
+    fn missing_days_are_left_out_of_the_line() {
+        let highs = [
+            Some(12.0),
+            None,
+            Some(13.0),
+            None,
+            None,
+            Some(15.0),
+            Some(11.0),
+        ];
+
+        let points = points(&render(&highs, "°C"));
+
+        assert_eq!(points.len(), 4);
+        // The third day keeps its place: 2 + 2 * 116 / 6, to one decimal.
+        assert_eq!(points[1].0, 40.7);
+    }
+
+    #[test]
+    fn a_flat_week_is_drawn_through_the_middle() {
+        let points = points(&render(&[Some(20.0); 7], "°F"));
+
+        assert!(points.iter().all(|&(_, y)| y == HEIGHT / 2.0));
+    }
+
+    #[test]
+    fn a_week_without_values_is_an_empty_line() {
+        let svg = render(&[None; 7], "°C");
+
+        assert!(points(&svg).is_empty());
+        assert!(svg.contains("<title>No daily highs available</title>"));
+    }
+}
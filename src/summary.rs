@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+use crate::Hourly;
+
+/// Daily and overall temperature aggregates for one forecast.
+#[derive(Serialize, Debug, Clone)]
+pub struct WeatherSummary {
+    pub city: String,
+    pub unit: &'static str,
+    pub days: Vec<DailySummary>,
+    /// Across the whole forecast; absent when it has no readings.
+    pub overall: Option<Aggregate>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DailySummary {
+    /// `YYYY-MM-DD`, in the forecast's timezone.
+    pub date: String,
+    #[serde(flatten)]
+    pub temperature: Aggregate,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Aggregate {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+}
+
+impl Aggregate {
+    fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let avg = values.iter().sum::<f64>() / values.len() as f64;
+        Some(Self { min, max, avg })
+    }
+}
+
+/// Groups the hourly temperatures by day. Expects `hourly` to have passed
+/// `check_alignment`, and its timestamps to be in order.
+pub fn summarize(city: &str, unit: &'static str, hourly: &Hourly) -> WeatherSummary {
+    let mut days: Vec<(String, Vec<f64>)> = Vec::new();
+    for (time, temperature) in hourly.time.iter().zip(&hourly.temperature_2m) {
+        // Hours without a reading don't count towards the aggregates.
+        let Some(temperature) = *temperature else {
+            continue;
+        };
+        let date = time.split_once('T').map_or(time.as_str(), |(date, _)| date);
+        match days.last_mut() {
+            Some((day, values)) if day == date => values.push(temperature),
+            _ => days.push((date.to_string(), vec![temperature])),
+        }
+    }
+
+    let readings: Vec<f64> = hourly.temperature_2m.iter().flatten().copied().collect();
+    WeatherSummary {
+        city: city.to_string(),
+        unit,
+        days: days
+            .into_iter()
+            .filter_map(|(date, values)| {
+                Some(DailySummary {
+                    date,
+                    temperature: Aggregate::of(&values)?,
+                })
+            })
+            .collect(),
+        overall: Aggregate::of(&readings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hourly(time: &[&str], temperature_2m: &[Option<f64>]) -> Hourly {
+        serde_json::from_value(serde_json::json!({
+            "time": time,
+            "temperature_2m": temperature_2m,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn temperatures_are_aggregated_per_day_and_overall() {
+        let hourly = hourly(
+            &[
+                "2024-05-01T12:00",
+                "2024-05-01T13:00",
+                "2024-05-02T12:00",
+                "2024-05-02T13:00",
+            ],
+            &[Some(10.0), Some(14.0), Some(20.0), Some(24.0)],
+        );
+
+        let summary = summarize("Berlin", "°C", &hourly);
+
+        let days: Vec<(&str, f64, f64, f64)> = summary
+            .days
+            .iter()
+            .map(|day| {
+                let t = day.temperature;
+                (day.date.as_str(), t.min, t.max, t.avg)
+            })
+            .collect();
+        assert_eq!(
+            days,
+            [
+                ("2024-05-01", 10.0, 14.0, 12.0),
+                ("2024-05-02", 20.0, 24.0, 22.0)
+            ]
+        );
+        let overall = summary.overall.unwrap();
+        assert_eq!((overall.min, overall.max, overall.avg), (10.0, 24.0, 17.0));
+    }
+
+    #[test]
+    fn hours_without_a_reading_are_left_out() {
+        let hourly = hourly(
+            &["2024-05-01T12:00", "2024-05-01T13:00", "2024-05-02T12:00"],
+            &[Some(10.0), None, None],
+        );
+
+        let summary = summarize("Berlin", "°C", &hourly);
+
+        assert_eq!(summary.days.len(), 1);
+        assert_eq!(summary.days[0].temperature.avg, 10.0);
+        assert_eq!(summary.overall.unwrap().avg, 10.0);
+    }
+}
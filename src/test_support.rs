@@ -0,0 +1,214 @@
+//! A local stand-in for the Open-Meteo APIs, and the app served against it,
+//! so that tests go through the router without touching the network.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    extract::Query,
+    http::{header, StatusCode, Uri},
+    Router,
+};
+use reqwest::{RequestBuilder, Url};
+
+use crate::{cache::GeoCache, config::Config, recording::Recording, upstream::Upstream, AppState};
+
+/// One request the mock upstream received.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub path: String,
+    pub params: HashMap<String, String>,
+}
+
+impl MockRequest {
+    /// The query parameter `name`, or `""` if it wasn't sent.
+    pub fn param(&self, name: &str) -> &str {
+        self.params.get(name).map_or("", String::as_str)
+    }
+}
+
+type Responder = dyn Fn(&MockRequest) -> (StatusCode, String) + Send + Sync;
+
+/// An HTTP server answering upstream requests with whatever the responder
+/// returns, and remembering every request it got.
+pub struct MockUpstream {
+    pub url: Url,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    /// Requests being answered right now, and the most there ever were.
+    in_flight: Arc<(AtomicUsize, AtomicUsize)>,
+}
+
+impl MockUpstream {
+    pub async fn start(
+        respond: impl Fn(&MockRequest) -> (StatusCode, String) + Send + Sync + 'static,
+    ) -> Self {
+        Self::start_with_delay(Duration::ZERO, respond).await
+    }
+
+    /// Like [`start`](Self::start), but every response takes `delay`.
+    pub async fn start_with_delay(
+        delay: Duration,
+        respond: impl Fn(&MockRequest) -> (StatusCode, String) + Send + Sync + 'static,
+    ) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let respond: Arc<Responder> = Arc::new(respond);
+        let in_flight = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let recorded = Arc::clone(&requests);
+        let counted = Arc::clone(&in_flight);
+        let app = Router::new().fallback(
+            move |uri: Uri, Query(params): Query<HashMap<String, String>>| {
+                let respond = Arc::clone(&respond);
+                let recorded = Arc::clone(&recorded);
+                let counted = Arc::clone(&counted);
+                async move {
+                    let (current, peak) = &*counted;
+                    peak.fetch_max(current.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    let request = MockRequest {
+                        path: uri.path().to_string(),
+                        params,
+                    };
+                    recorded.lock().unwrap().push(request.clone());
+                    tokio::time::sleep(delay).await;
+                    let (status, body) = respond(&request);
+                    current.fetch_sub(1, Ordering::SeqCst);
+                    (status, [(header::CONTENT_TYPE, "application/json")], body)
+                }
+            },
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Self {
+            url: Url::parse(&format!("http://{address}")).unwrap(),
+            requests,
+            in_flight,
+        }
+    }
+
+    /// Serves the captured responses in `tests/fixtures`: Berlin geocodes,
+    /// `Rejected` gets an error body, any other name has no results, and
+    /// every forecast is Berlin's.
+    pub async fn open_meteo() -> Self {
+        Self::start(open_meteo).await
+    }
+
+    /// The requests received for `path`, oldest first.
+    pub fn requests(&self, path: &str) -> Vec<MockRequest> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .filter(|r| r.path == path)
+            .cloned()
+            .collect()
+    }
+
+    /// How many requests were received for `path`.
+    pub fn calls(&self, path: &str) -> usize {
+        self.requests(path).len()
+    }
+
+    /// The most requests, on any path, that were being answered at once.
+    pub fn peak_concurrency(&self) -> usize {
+        self.in_flight.1.load(Ordering::SeqCst)
+    }
+}
+
+/// The contents of `tests/fixtures/<name>`.
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+}
+
+/// The responder behind [`MockUpstream::open_meteo`].
+pub fn open_meteo(request: &MockRequest) -> (StatusCode, String) {
+    match (request.path.as_str(), request.param("name")) {
+        ("/v1/search", "Berlin") => (StatusCode::OK, fixture("geocoding_berlin.json")),
+        ("/v1/search", "Rejected") => (StatusCode::OK, fixture("geocoding_error.json")),
+        ("/v1/search", _) => (StatusCode::OK, fixture("geocoding_no_results.json")),
+        ("/v1/forecast", _) => (StatusCode::OK, fixture("forecast_berlin.json")),
+        _ => (StatusCode::NOT_FOUND, String::new()),
+    }
+}
+
+/// The Berlin forecast fixture with an extra hourly series `name` in `unit`.
+pub fn forecast_with_series(name: &str, values: serde_json::Value, unit: &str) -> String {
+    let mut forecast: serde_json::Value =
+        serde_json::from_str(&fixture("forecast_berlin.json")).unwrap();
+    forecast["hourly"][name] = values;
+    forecast["hourly_units"][name] = unit.into();
+    forecast.to_string()
+}
+
+/// An empty directory for `test` under the system's temporary directory.
+pub fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("weather-{}-{test}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// App state with a throwaway database, calling `mock` instead of Open-Meteo.
+pub fn state(config: Config, mock: &MockUpstream) -> AppState {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let upstream = Upstream::new(
+        client,
+        Duration::from_secs(config.upstream_stats_window_secs),
+        0.0,
+        Recording::Off,
+        config.max_upstream_body_bytes,
+    )
+    .with_origin(mock.url.clone());
+    let geo_cache = Arc::new(GeoCache::new(db.clone()));
+    AppState::new(config, db, geo_cache, upstream)
+}
+
+/// The full router, listening on a local port.
+pub struct TestApp {
+    pub state: AppState,
+    address: SocketAddr,
+    client: reqwest::Client,
+}
+
+impl TestApp {
+    pub async fn serve(state: AppState) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = crate::app(state.clone());
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+        Self {
+            state,
+            address,
+            client: reqwest::Client::builder().no_proxy().build().unwrap(),
+        }
+    }
+
+    pub fn url(&self, path_and_query: &str) -> String {
+        format!("http://{}{path_and_query}", self.address)
+    }
+
+    pub async fn get(&self, path_and_query: &str) -> reqwest::Response {
+        self.client
+            .get(self.url(path_and_query))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// A request builder, for anything beyond a plain `GET`.
+    pub fn request(&self, method: reqwest::Method, path_and_query: &str) -> RequestBuilder {
+        self.client.request(method, self.url(path_and_query))
+    }
+}
@@ -0,0 +1,349 @@
+//! Unit conversions applied server-side when a client asks for a unit other
+//! than the one Open-Meteo returned.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// The unit as Open-Meteo writes it in `hourly_units`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+}
+
+/// A temperature together with the unit it is expressed in, so that values
+/// in different units can't be mixed up. Serializes as `{"value", "unit"}`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Temperature {
+    pub value: f64,
+    pub unit: TemperatureUnit,
+}
+
+impl Temperature {
+    pub fn new(value: f64, unit: TemperatureUnit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn celsius(value: f64) -> Self {
+        Self::new(value, TemperatureUnit::Celsius)
+    }
+
+    /// The same temperature expressed in `unit`.
+    pub fn to(self, unit: TemperatureUnit) -> Self {
+        let value = match (self.unit, unit) {
+            (TemperatureUnit::Celsius, TemperatureUnit::Fahrenheit) => {
+                celsius_to_fahrenheit(self.value)
+            }
+            (TemperatureUnit::Fahrenheit, TemperatureUnit::Celsius) => {
+                fahrenheit_to_celsius(self.value)
+            }
+            _ => self.value,
+        };
+        Self::new(value, unit)
+    }
+}
+
+pub fn celsius_to_fahrenheit(celsius: f64) -> f64 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+pub fn fahrenheit_to_celsius(fahrenheit: f64) -> f64 {
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+pub fn kmh_to_mph(kmh: f64) -> f64 {
+    kmh / 1.609_344
+}
+
+pub fn mph_to_kmh(mph: f64) -> f64 {
+    mph * 1.609_344
+}
+
+pub fn kmh_to_ms(kmh: f64) -> f64 {
+    kmh / 3.6
+}
+
+pub fn ms_to_kmh(ms: f64) -> f64 {
+    ms * 3.6
+}
+
+pub fn kmh_to_knots(kmh: f64) -> f64 {
+    kmh / 1.852
+}
+
+pub fn knots_to_kmh(knots: f64) -> f64 {
+    knots * 1.852
+}
+
+pub fn mm_to_inches(mm: f64) -> f64 {
+    mm / 25.4
+}
+
+pub fn inches_to_mm(inches: f64) -> f64 {
+    inches * 25.4
+}
+
+/// Wind speed units, named like Open-Meteo's `wind_speed_unit` parameter.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindSpeedUnit {
+    #[default]
+    Kmh,
+    Ms,
+    Mph,
+    Kn,
+}
+
+impl WindSpeedUnit {
+    /// The unit as Open-Meteo writes it in `hourly_units`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            WindSpeedUnit::Kmh => "km/h",
+            WindSpeedUnit::Ms => "m/s",
+            WindSpeedUnit::Mph => "mph",
+            WindSpeedUnit::Kn => "kn",
+        }
+    }
+
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        [Self::Kmh, Self::Ms, Self::Mph, Self::Kn]
+            .into_iter()
+            .find(|unit| unit.symbol() == symbol)
+    }
+
+    /// Converts `value`, a speed in `from`, into this unit.
+    pub fn convert(self, value: f64, from: Self) -> f64 {
+        let kmh = match from {
+            WindSpeedUnit::Kmh => value,
+            WindSpeedUnit::Ms => ms_to_kmh(value),
+            WindSpeedUnit::Mph => mph_to_kmh(value),
+            WindSpeedUnit::Kn => knots_to_kmh(value),
+        };
+        match self {
+            WindSpeedUnit::Kmh => kmh,
+            WindSpeedUnit::Ms => kmh_to_ms(kmh),
+            WindSpeedUnit::Mph => kmh_to_mph(kmh),
+            WindSpeedUnit::Kn => kmh_to_knots(kmh),
+        }
+    }
+}
+
+/// Precipitation units, named like Open-Meteo's `precipitation_unit`
+/// parameter.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PrecipitationUnit {
+    #[default]
+    Mm,
+    Inch,
+}
+
+impl PrecipitationUnit {
+    /// The unit as Open-Meteo writes it in `hourly_units`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PrecipitationUnit::Mm => "mm",
+            PrecipitationUnit::Inch => "inch",
+        }
+    }
+
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        [Self::Mm, Self::Inch]
+            .into_iter()
+            .find(|unit| unit.symbol() == symbol)
+    }
+
+    /// Converts `value`, an amount in `from`, into this unit.
+    pub fn convert(self, value: f64, from: Self) -> f64 {
+        match (from, self) {
+            (PrecipitationUnit::Mm, PrecipitationUnit::Inch) => mm_to_inches(value),
+            (PrecipitationUnit::Inch, PrecipitationUnit::Mm) => inches_to_mm(value),
+            _ => value,
+        }
+    }
+}
+
+/// Rounds half away from zero to the given number of decimal places.
+pub fn round_to(value: f64, decimals: u32) -> f64 {
+    let factor = 10_f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Most decimal places a client may ask numeric values to be rounded to.
+pub const MAX_PRECISION: u32 = 3;
+
+pub fn validate_precision(decimals: u32) -> Result<u32, String> {
+    if decimals <= MAX_PRECISION {
+        Ok(decimals)
+    } else {
+        Err(format!(
+            "precision must be between 0 and {MAX_PRECISION} decimal places"
+        ))
+    }
+}
+
+/// Converts a Celsius reading (as returned by Open-Meteo) into `unit`.
+pub fn convert_temperature(celsius: f64, unit: TemperatureUnit) -> f64 {
+    Temperature::celsius(celsius).to(unit).value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_celsius_and_fahrenheit() {
+        assert_eq!(celsius_to_fahrenheit(0.0), 32.0);
+        assert_eq!(celsius_to_fahrenheit(100.0), 212.0);
+        assert_eq!(celsius_to_fahrenheit(-40.0), -40.0);
+        assert_eq!(fahrenheit_to_celsius(212.0), 100.0);
+        assert_eq!(fahrenheit_to_celsius(-40.0), -40.0);
+    }
+
+    #[test]
+    fn celsius_readings_are_converted_into_the_requested_unit() {
+        assert_eq!(convert_temperature(20.0, TemperatureUnit::Celsius), 20.0);
+        assert_eq!(convert_temperature(20.0, TemperatureUnit::Fahrenheit), 68.0);
+    }
+
+    #[test]
+    fn a_temperature_keeps_the_unit_it_was_made_with() {
+        let reading = Temperature::new(68.0, TemperatureUnit::Fahrenheit);
+
+        assert_eq!(reading.value, 68.0);
+        assert_eq!(reading.unit, TemperatureUnit::Fahrenheit);
+        assert_eq!(
+            Temperature::celsius(20.0),
+            Temperature::new(20.0, TemperatureUnit::Celsius)
+        );
+    }
+
+    #[test]
+    fn temperatures_convert_into_either_unit() {
+        let celsius = Temperature::celsius(100.0);
+
+        let fahrenheit = celsius.to(TemperatureUnit::Fahrenheit);
+
+        assert_eq!(
+            fahrenheit,
+            Temperature::new(212.0, TemperatureUnit::Fahrenheit)
+        );
+        assert_eq!(fahrenheit.to(TemperatureUnit::Celsius), celsius);
+        assert_eq!(celsius.to(TemperatureUnit::Celsius), celsius);
+        assert_eq!(fahrenheit.to(TemperatureUnit::Fahrenheit), fahrenheit);
+    }
+
+    #[test]
+    fn temperatures_serialize_as_value_and_unit() {
+        let json =
+            serde_json::to_value(Temperature::new(-3.5, TemperatureUnit::Fahrenheit)).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({ "value": -3.5, "unit": "fahrenheit" })
+        );
+    }
+
+    /// Whether `a` and `b` agree to within rounding error.
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn converts_wind_speeds_with_known_values() {
+        assert!(close(kmh_to_mph(1.609_344), 1.0));
+        assert!(close(mph_to_kmh(60.0), 96.56064));
+        assert!(close(kmh_to_ms(36.0), 10.0));
+        assert!(close(ms_to_kmh(10.0), 36.0));
+        assert!(close(kmh_to_knots(1.852), 1.0));
+        assert!(close(knots_to_kmh(10.0), 18.52));
+        assert_eq!(round_to(kmh_to_mph(100.0), 1), 62.1);
+    }
+
+    #[test]
+    fn converts_precipitation_with_known_values() {
+        assert!(close(mm_to_inches(25.4), 1.0));
+        assert!(close(inches_to_mm(2.0), 50.8));
+        assert_eq!(round_to(mm_to_inches(10.0), 2), 0.39);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for value in [0.0, 0.1, 12.5, 100.0] {
+            assert!(close(mph_to_kmh(kmh_to_mph(value)), value));
+            assert!(close(ms_to_kmh(kmh_to_ms(value)), value));
+            assert!(close(knots_to_kmh(kmh_to_knots(value)), value));
+            assert!(close(inches_to_mm(mm_to_inches(value)), value));
+        }
+    }
+
+    #[test]
+    fn wind_speeds_convert_between_any_two_units() {
+        assert!(close(
+            WindSpeedUnit::Mph.convert(36.0, WindSpeedUnit::Kmh),
+            22.369_362_920_544_02
+        ));
+        assert!(close(
+            WindSpeedUnit::Kmh.convert(10.0, WindSpeedUnit::Ms),
+            36.0
+        ));
+        assert!(close(
+            WindSpeedUnit::Kn.convert(10.0, WindSpeedUnit::Ms),
+            19.438_444_924_406_05
+        ));
+        assert_eq!(WindSpeedUnit::Ms.convert(7.5, WindSpeedUnit::Ms), 7.5);
+    }
+
+    #[test]
+    fn precipitation_converts_only_between_different_units() {
+        assert!(close(
+            PrecipitationUnit::Inch.convert(12.7, PrecipitationUnit::Mm),
+            0.5
+        ));
+        assert!(close(
+            PrecipitationUnit::Mm.convert(0.5, PrecipitationUnit::Inch),
+            12.7
+        ));
+        assert_eq!(
+            PrecipitationUnit::Mm.convert(1.2, PrecipitationUnit::Mm),
+            1.2
+        );
+    }
+
+    #[test]
+    fn units_are_read_from_open_meteo_symbols() {
+        assert_eq!(WindSpeedUnit::from_symbol("km/h"), Some(WindSpeedUnit::Kmh));
+        assert_eq!(WindSpeedUnit::from_symbol("kn"), Some(WindSpeedUnit::Kn));
+        assert_eq!(WindSpeedUnit::from_symbol("knots"), None);
+        assert_eq!(
+            PrecipitationUnit::from_symbol("inch"),
+            Some(PrecipitationUnit::Inch)
+        );
+        assert_eq!(PrecipitationUnit::from_symbol("cm"), None);
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(round_to(2.25, 1), 2.3);
+        assert_eq!(round_to(-2.25, 1), -2.3);
+        assert_eq!(round_to(18.449, 2), 18.45);
+        assert_eq!(round_to(18.5, 0), 19.0);
+    }
+
+    #[test]
+    fn precision_is_limited_to_the_maximum() {
+        assert_eq!(validate_precision(0), Ok(0));
+        assert_eq!(validate_precision(MAX_PRECISION), Ok(MAX_PRECISION));
+        assert!(validate_precision(MAX_PRECISION + 1).is_err());
+    }
+}
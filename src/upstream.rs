@@ -0,0 +1,905 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::{StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    chaos::FailureInjector,
+    error::ApiError,
+    recording::{self, Recording},
+};
+
+/// Most samples kept per API, however busy the window is.
+const MAX_SAMPLES: usize = 10_000;
+
+/// Attempts per upstream call, including the first.
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; later retries wait proportionally longer.
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Retries allowed in a burst, shared by all requests.
+const RETRY_BUDGET: f64 = 20.0;
+/// Retries regained per second while the budget isn't full.
+const RETRY_BUDGET_REFILL_PER_SEC: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Api {
+    Geocoding,
+    Forecast,
+}
+
+impl Api {
+    /// The one endpoint each API is called at. Nothing a client sends ever
+    /// becomes part of it.
+    fn endpoint(self) -> &'static str {
+        match self {
+            Api::Geocoding => "https://geocoding-api.open-meteo.com/v1/search",
+            Api::Forecast => "https://api.open-meteo.com/v1/forecast",
+        }
+    }
+
+    /// Builds a request URL for this API. Values are percent-encoded, so
+    /// user input such as a city name can neither add parameters nor change
+    /// where the request goes.
+    pub fn url<K, V>(self, params: impl IntoIterator<Item = (K, V)>) -> Url
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut url = Url::parse(self.endpoint()).expect("API endpoints are valid URLs");
+        url.query_pairs_mut().extend_pairs(params);
+        url
+    }
+}
+
+/// Builds the HTTP client for upstream calls. Certificates in the PEM file
+/// at `ca_bundle` are trusted in addition to the system roots, for networks
+/// behind a TLS-inspecting proxy.
+///
+/// All calls go through `proxy` if given. Otherwise `HTTP_PROXY` and
+/// `HTTPS_PROXY` are honored as usual. SOCKS5 proxies need the `socks`
+/// feature.
+pub fn http_client(
+    ca_bundle: Option<&Path>,
+    proxy: Option<&str>,
+) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        if proxy.starts_with("socks") && !cfg!(feature = "socks") {
+            return Err(
+                "this build has no SOCKS support; rebuild with `--features socks`".to_string(),
+            );
+        }
+        let proxy = reqwest::Proxy::all(proxy).map_err(|e| format!("{proxy}: {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = ca_bundle {
+        let pem = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        if certificates.is_empty() {
+            return Err(format!("{} contains no certificates", path.display()));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// HTTP access to the Open-Meteo APIs, shared by every handler.
+pub struct Upstream {
+    client: reqwest::Client,
+    geocoding: LatencyWindow,
+    forecast: LatencyWindow,
+    retry_budget: RetryBudget,
+    chaos: FailureInjector,
+    recording: Recording,
+    max_body_bytes: usize,
+    /// Where requests actually go, in place of the Open-Meteo hosts. Only
+    /// tests set it, to point at a local mock server.
+    origin: Option<Url>,
+}
+
+impl Upstream {
+    pub fn new(
+        client: reqwest::Client,
+        stats_window: Duration,
+        chaos_failure_rate: f64,
+        recording: Recording,
+        max_body_bytes: usize,
+    ) -> Self {
+        Self {
+            client,
+            geocoding: LatencyWindow::new(stats_window),
+            forecast: LatencyWindow::new(stats_window),
+            retry_budget: RetryBudget::new(RETRY_BUDGET, RETRY_BUDGET_REFILL_PER_SEC),
+            chaos: FailureInjector::new(chaos_failure_rate),
+            recording,
+            max_body_bytes,
+            origin: None,
+        }
+    }
+
+    /// Sends every request to `origin` instead, keeping path and query.
+    #[cfg(test)]
+    pub fn with_origin(mut self, origin: Url) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Replaces the shared retry budget, so tests can run it dry quickly.
+    #[cfg(test)]
+    pub fn with_retry_budget(mut self, capacity: f64, refill_per_sec: f64) -> Self {
+        self.retry_budget = RetryBudget::new(capacity, refill_per_sec);
+        self
+    }
+
+    /// Fetches `url` and decodes the JSON body, recording latency and outcome.
+    ///
+    /// Transient failures (connection errors, empty bodies, server errors)
+    /// are retried with a short backoff, as long as the shared retry budget
+    /// lasts. During a broad outage it runs dry and calls fail fast.
+    ///
+    /// The total latency and outcome are recorded as the `latency_ms` and
+    /// `status` fields of the current span, where the caller declared them.
+    pub async fn get_json<T: DeserializeOwned>(&self, api: Api, url: &Url) -> Result<T, ApiError> {
+        // Only URLs built by `Api::url` for the same API are ever sent.
+        if url.as_str().split(['?', '#']).next() != Some(api.endpoint()) {
+            return Err(ApiError::ExternalApiError(format!(
+                "refusing to send a {api:?} request to {url}"
+            )));
+        }
+        let start = Instant::now();
+        let result = self.get_json_with_retries(api, url).await;
+        let span = tracing::Span::current();
+        span.record("latency_ms", start.elapsed().as_millis() as u64);
+        span.record("status", if result.is_ok() { "ok" } else { "error" });
+        result
+    }
+
+    async fn get_json_with_retries<T: DeserializeOwned>(
+        &self,
+        api: Api,
+        url: &Url,
+    ) -> Result<T, ApiError> {
+        let mut attempt = 1;
+        loop {
+            let start = Instant::now();
+            let result = self.fetch(url).await;
+            self.window(api).record(start.elapsed(), result.is_ok());
+            match result {
+                Ok(value) => return Ok(value),
+                Err(failure)
+                    if failure.retryable
+                        && attempt < MAX_ATTEMPTS
+                        && self.retry_budget.try_spend() =>
+                {
+                    tracing::warn!(
+                        "{api:?} request failed on attempt {attempt}, retrying: {}",
+                        failure.error
+                    );
+                    tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+                    attempt += 1;
+                }
+                Err(failure) => return Err(failure.error),
+            }
+        }
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, url: &Url) -> Result<T, Failure> {
+        if self.chaos.should_fail() {
+            return Err(Failure::transient(
+                "injected failure (CHAOS_FAILURE_RATE)".to_string(),
+            ));
+        }
+        let (status, body) = match &self.recording {
+            Recording::Replay(dir) => {
+                let exchange = recording::load(dir, url).map_err(|e| Failure {
+                    error: ApiError::ExternalApiError(e),
+                    retryable: false,
+                })?;
+                let status = StatusCode::from_u16(exchange.status)
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                (status, exchange.body.into_bytes())
+            }
+            _ => self.send(url).await?,
+        };
+
+        // An error status fails the call whatever the body holds, even when
+        // it's JSON that happens to decode. Only server errors are retried.
+        if !status.is_success() {
+            let reason = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|body| body["reason"].as_str().map(|reason| format!(": {reason}")))
+                .unwrap_or_default();
+            return Err(Failure {
+                error: ApiError::ExternalApiError(format!(
+                    "upstream returned HTTP {status}{reason}"
+                )),
+                retryable: status.is_server_error(),
+            });
+        }
+        // Open-Meteo occasionally answers `200` with nothing in the body.
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Err(Failure::transient(format!(
+                "upstream returned an empty response body (HTTP {status})"
+            )));
+        }
+        serde_json::from_slice(&body).map_err(|e| Failure {
+            error: ApiError::ExternalApiError(format!(
+                "upstream returned an invalid response body (HTTP {status}): {e}"
+            )),
+            retryable: false,
+        })
+    }
+
+    async fn send(&self, url: &Url) -> Result<(StatusCode, Vec<u8>), Failure> {
+        let mut response = self
+            .client
+            .get(self.target(url))
+            .send()
+            .await
+            .map_err(|e| Failure::transient(e.to_string()))?;
+        let status = response.status();
+        // Read the body chunk by chunk, so an oversized one is rejected
+        // before it is held in memory in full.
+        let too_large = || Failure {
+            error: ApiError::ExternalApiError(format!(
+                "upstream response body exceeds {} bytes (HTTP {status})",
+                self.max_body_bytes
+            )),
+            retryable: false,
+        };
+        if response
+            .content_length()
+            .is_some_and(|length| length > self.max_body_bytes as u64)
+        {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| Failure::transient(e.to_string()))?
+        {
+            if body.len() + chunk.len() > self.max_body_bytes {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        if let Recording::Record(dir) = &self.recording {
+            if let Err(e) = recording::save(dir, url, status.as_u16(), &body) {
+                tracing::warn!("Failed to record the response to {url}: {e}");
+            }
+        }
+        Ok((status, body))
+    }
+
+    /// `url`, redirected to the test origin if one is set.
+    fn target(&self, url: &Url) -> Url {
+        let Some(origin) = &self.origin else {
+            return url.clone();
+        };
+        let mut target = origin.clone();
+        target.set_path(url.path());
+        target.set_query(url.query());
+        target
+    }
+
+    fn window(&self, api: Api) -> &LatencyWindow {
+        match api {
+            Api::Geocoding => &self.geocoding,
+            Api::Forecast => &self.forecast,
+        }
+    }
+
+    pub fn stats(&self) -> UpstreamStats {
+        UpstreamStats {
+            window_secs: self.geocoding.window.as_secs(),
+            geocoding: self.geocoding.summary(),
+            forecast: self.forecast.summary(),
+            retry_budget: self.retry_budget.summary(),
+        }
+    }
+}
+
+/// A failed upstream attempt, and whether trying again might succeed.
+struct Failure {
+    error: ApiError,
+    retryable: bool,
+}
+
+impl Failure {
+    fn transient(message: String) -> Self {
+        Self {
+            error: ApiError::ExternalApiError(message),
+            retryable: true,
+        }
+    }
+}
+
+/// Token bucket limiting how many retries all requests together may make.
+struct RetryBudget {
+    capacity: f64,
+    refill_per_sec: f64,
+    /// Tokens left, and when they were last topped up.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RetryBudget {
+    fn new(capacity: f64, refill_per_sec: f64) -> Self {
+        Self {
+            capacity,
+            refill_per_sec,
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    fn available(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        state.0
+    }
+
+    /// Takes one retry from the budget, or returns `false` if it's empty.
+    fn try_spend(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        if state.0 < 1.0 {
+            return false;
+        }
+        state.0 -= 1.0;
+        true
+    }
+
+    fn refill(&self, (tokens, refilled_at): &mut (f64, Instant)) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(*refilled_at).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        *refilled_at = now;
+    }
+
+    fn summary(&self) -> RetryBudgetSummary {
+        RetryBudgetSummary {
+            available: self.available(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// Latency samples for one API over a rolling time window.
+struct LatencyWindow {
+    window: Duration,
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+struct Sample {
+    at: Instant,
+    latency: Duration,
+    ok: bool,
+}
+
+impl LatencyWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn record(&self, latency: Duration, ok: bool) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            at: Instant::now(),
+            latency,
+            ok,
+        });
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let mut samples = self.samples.lock().unwrap();
+        while samples
+            .front()
+            .is_some_and(|sample| sample.at.elapsed() > self.window)
+        {
+            samples.pop_front();
+        }
+
+        let mut latencies: Vec<f64> = samples
+            .iter()
+            .map(|sample| sample.latency.as_secs_f64() * 1000.0)
+            .collect();
+        latencies.sort_by(f64::total_cmp);
+        let errors = samples.iter().filter(|sample| !sample.ok).count();
+
+        LatencySummary {
+            samples: latencies.len(),
+            p50_ms: percentile(&latencies, 0.50),
+            p95_ms: percentile(&latencies, 0.95),
+            p99_ms: percentile(&latencies, 0.99),
+            error_rate: if latencies.is_empty() {
+                0.0
+            } else {
+                errors as f64 / latencies.len() as f64
+            },
+        }
+    }
+}
+
+/// Nearest-rank percentile of already sorted values.
+fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[derive(Serialize)]
+pub struct UpstreamStats {
+    window_secs: u64,
+    geocoding: LatencySummary,
+    forecast: LatencySummary,
+    retry_budget: RetryBudgetSummary,
+}
+
+#[derive(Serialize)]
+pub struct RetryBudgetSummary {
+    available: f64,
+    capacity: f64,
+}
+
+#[derive(Serialize)]
+pub struct LatencySummary {
+    samples: usize,
+    p50_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    p99_ms: Option<f64>,
+    error_rate: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support::{self, MockUpstream};
+
+    /// An upstream sending every request to `mock`, with a retry budget of
+    /// `budget` that never refills.
+    fn upstream(mock: &MockUpstream, budget: f64) -> Upstream {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        Upstream::new(
+            client,
+            Duration::from_secs(60),
+            0.0,
+            Recording::Off,
+            1 << 20,
+        )
+        .with_origin(mock.url.clone())
+        .with_retry_budget(budget, 0.0)
+    }
+
+    async fn fetch(upstream: &Upstream) -> Result<Value, ApiError> {
+        let url = Api::Forecast.url([("latitude", "52.52")]);
+        upstream.get_json(Api::Forecast, &url).await
+    }
+
+    #[tokio::test]
+    async fn at_a_chaos_rate_of_one_every_call_fails_without_reaching_upstream() {
+        let mock = MockUpstream::start(|_| (StatusCode::OK, "{}".to_string())).await;
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let upstream = Upstream::new(
+            client,
+            Duration::from_secs(60),
+            1.0,
+            Recording::Off,
+            1 << 20,
+        )
+        .with_origin(mock.url.clone());
+
+        for _ in 0..5 {
+            let error = fetch(&upstream).await.unwrap_err().to_string();
+            assert!(error.contains("injected failure"), "{error}");
+        }
+
+        assert_eq!(mock_total(&mock), 0);
+    }
+
+    /// City names and coordinates crafted to look like URL syntax.
+    const CRAFTED: &[&str] = &[
+        "evil.com/?",
+        "evil.com#",
+        "user@evil.com",
+        "..%2F..%2Fadmin",
+        "Berlin&latitude=0",
+        "52.5#@evil.com",
+        "//evil.com/v1/search",
+    ];
+
+    #[test]
+    fn crafted_parameters_never_change_the_endpoint() {
+        for api in [Api::Geocoding, Api::Forecast] {
+            let endpoint = Url::parse(api.endpoint()).unwrap();
+            for &input in CRAFTED {
+                let url = api.url([("name", input), ("latitude", input)]);
+
+                assert_eq!(url.host_str(), endpoint.host_str(), "{input}");
+                assert_eq!(url.path(), endpoint.path(), "{input}");
+                assert_eq!(url.fragment(), None, "{input}");
+                let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+                assert_eq!(
+                    params,
+                    [
+                        ("name".to_string(), input.to_string()),
+                        ("latitude".to_string(), input.to_string())
+                    ],
+                    "{input}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn crafted_parameters_reach_the_endpoint_unchanged() {
+        let mock = MockUpstream::start(|_| (StatusCode::OK, "{}".to_string())).await;
+        let upstream = upstream(&mock, RETRY_BUDGET);
+
+        for &input in CRAFTED {
+            let url = Api::Geocoding.url([("name", input)]);
+            let _: Value = upstream.get_json(Api::Geocoding, &url).await.unwrap();
+        }
+
+        let requests = mock.requests("/v1/search");
+        let names: Vec<&str> = requests.iter().map(|r| r.param("name")).collect();
+        assert_eq!(names, CRAFTED);
+        assert_eq!(mock_total(&mock), CRAFTED.len());
+    }
+
+    /// Every request the mock got, whatever its path.
+    fn mock_total(mock: &MockUpstream) -> usize {
+        ["/v1/search", "/v1/forecast"]
+            .iter()
+            .map(|path| mock.calls(path))
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn urls_not_at_the_api_endpoint_are_refused() {
+        let mock = MockUpstream::start(|_| (StatusCode::OK, "{}".to_string())).await;
+        let upstream = upstream(&mock, RETRY_BUDGET);
+        let urls = [
+            "https://evil.com/v1/forecast?latitude=52.52",
+            "https://api.open-meteo.com.evil.com/v1/forecast",
+            "https://user@api.open-meteo.com/v1/forecast",
+            "https://api.open-meteo.com/v1/forecast/../search",
+            "https://api.open-meteo.com/v1/forecast%2F..%2Fsearch",
+            "https://api.open-meteo.com:8443/v1/forecast",
+            "http://api.open-meteo.com/v1/forecast",
+            "https://geocoding-api.open-meteo.com/v1/search?name=Berlin",
+        ];
+
+        for url in urls {
+            let url = Url::parse(url).unwrap();
+            let result: Result<Value, ApiError> = upstream.get_json(Api::Forecast, &url).await;
+
+            let error = result.unwrap_err().to_string();
+            assert!(error.contains("refusing to send"), "{url}: {error}");
+        }
+        assert_eq!(mock_total(&mock), 0);
+    }
+
+    fn recording_upstream(mock: &MockUpstream, recording: Recording) -> Upstream {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        Upstream::new(client, Duration::from_secs(60), 0.0, recording, 1 << 20)
+            .with_origin(mock.url.clone())
+    }
+
+    #[tokio::test]
+    async fn a_recorded_response_is_replayed_without_reaching_upstream() {
+        let dir = test_support::temp_dir("record-replay");
+        let mock = MockUpstream::open_meteo().await;
+        let recorder = recording_upstream(&mock, Recording::Record(dir.clone()));
+        let replayer = recording_upstream(&mock, Recording::Replay(dir));
+        let url = Api::Geocoding.url([("name", "Berlin"), ("count", "5")]);
+        let reordered = Api::Geocoding.url([("count", "5"), ("name", "Berlin")]);
+
+        let recorded: Value = recorder.get_json(Api::Geocoding, &url).await.unwrap();
+        let replayed: Value = replayer.get_json(Api::Geocoding, &reordered).await.unwrap();
+
+        assert_eq!(replayed, recorded);
+        assert_eq!(mock.calls("/v1/search"), 1);
+    }
+
+    #[tokio::test]
+    async fn replaying_an_unrecorded_request_fails_without_reaching_upstream() {
+        let mock = MockUpstream::open_meteo().await;
+        let replayer = recording_upstream(
+            &mock,
+            Recording::Replay(test_support::temp_dir("replay-missing")),
+        );
+
+        let error = fetch(&replayer).await.unwrap_err().to_string();
+
+        assert!(error.contains("no recording of"), "{error}");
+        assert_eq!(mock_total(&mock), 0);
+    }
+
+    #[test]
+    fn the_client_is_built_with_the_roots_from_a_ca_bundle() {
+        let bundle = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/test_ca.pem");
+
+        assert!(http_client(Some(&bundle), None).is_ok());
+        assert!(http_client(None, None).is_ok());
+    }
+
+    #[test]
+    fn a_missing_or_empty_ca_bundle_is_an_error() {
+        let dir = test_support::temp_dir("ca-bundle");
+        let missing = dir.join("missing.pem");
+        let empty = dir.join("empty.pem");
+        std::fs::write(&empty, "not a certificate\n").unwrap();
+
+        let missing_error = http_client(Some(&missing), None).unwrap_err();
+        let empty_error = http_client(Some(&empty), None).unwrap_err();
+
+        assert!(
+            missing_error.starts_with(&missing.display().to_string()),
+            "{missing_error}"
+        );
+        assert_eq!(
+            empty_error,
+            format!("{} contains no certificates", empty.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_traverse_the_configured_proxy() {
+        let proxy = MockUpstream::open_meteo().await;
+        let client = http_client(None, Some(proxy.url.as_str())).unwrap();
+
+        let response = client
+            .get("http://geocoding.invalid/v1/search?name=Berlin")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(proxy.requests("/v1/search")[0].param("name"), "Berlin");
+    }
+
+    #[test]
+    fn an_invalid_proxy_is_an_error() {
+        let error = http_client(None, Some("http://[::1")).unwrap_err();
+
+        assert!(error.starts_with("http://[::1: "), "{error}");
+    }
+
+    #[cfg(not(feature = "socks"))]
+    #[test]
+    fn socks_proxies_need_the_socks_feature() {
+        let error = http_client(None, Some("socks5://127.0.0.1:1080")).unwrap_err();
+
+        assert!(error.contains("--features socks"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn client_errors_with_a_json_body_fail_without_retrying() {
+        let mock = MockUpstream::start(|_| {
+            let body = r#"{"error": true, "reason": "Latitude must be in range"}"#;
+            (StatusCode::BAD_REQUEST, body.to_string())
+        })
+        .await;
+        let upstream = upstream(&mock, RETRY_BUDGET);
+
+        let error = fetch(&upstream).await.unwrap_err().to_string();
+
+        assert!(error.contains("HTTP 400"), "{error}");
+        assert!(error.contains("Latitude must be in range"), "{error}");
+        assert_eq!(mock.calls("/v1/forecast"), 1);
+    }
+
+    #[tokio::test]
+    async fn server_errors_with_a_json_body_are_retried() {
+        let mock = MockUpstream::start(|_| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"latitude": 52.52}"#.to_string(),
+            )
+        })
+        .await;
+        let upstream = upstream(&mock, RETRY_BUDGET);
+
+        let error = fetch(&upstream).await.unwrap_err().to_string();
+
+        assert!(error.contains("HTTP 503"), "{error}");
+        assert_eq!(mock.calls("/v1/forecast"), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn a_retried_server_error_can_still_succeed() {
+        let calls = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&calls);
+        let mock = MockUpstream::start(move |_| {
+            let mut calls = counter.lock().unwrap();
+            *calls += 1;
+            match *calls {
+                1 => (StatusCode::BAD_GATEWAY, String::new()),
+                _ => (StatusCode::OK, r#"{"latitude": 52.52}"#.to_string()),
+            }
+        })
+        .await;
+        let upstream = upstream(&mock, RETRY_BUDGET);
+
+        let body = fetch(&upstream).await.unwrap();
+
+        assert_eq!(body["latitude"], 52.52);
+        assert_eq!(mock.calls("/v1/forecast"), 2);
+    }
+
+    /// An upstream whose limit is `max_body_bytes`, sending every request to
+    /// `origin`.
+    fn limited_upstream(origin: Url, max_body_bytes: usize) -> Upstream {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        Upstream::new(
+            client,
+            Duration::from_secs(60),
+            0.0,
+            Recording::Off,
+            max_body_bytes,
+        )
+        .with_origin(origin)
+    }
+
+    /// A server answering every connection with `body` in chunks, without a
+    /// `Content-Length`.
+    async fn serve_chunked(body: &'static str) -> Url {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await;
+                let mut response = String::from(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     transfer-encoding: chunked\r\nconnection: close\r\n\r\n",
+                );
+                for chunk in body.as_bytes().chunks(8) {
+                    let chunk = std::str::from_utf8(chunk).unwrap();
+                    response.push_str(&format!("{:x}\r\n{chunk}\r\n", chunk.len()));
+                }
+                response.push_str("0\r\n\r\n");
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    const BODY: &str = r#"{"latitude": 52.52, "longitude": 13.41}"#;
+
+    #[tokio::test]
+    async fn a_body_over_the_limit_is_an_error_without_retrying() {
+        let mock = MockUpstream::start(|_| (StatusCode::OK, BODY.to_string())).await;
+        let upstream = limited_upstream(mock.url.clone(), BODY.len() - 1);
+
+        let error = fetch(&upstream).await.unwrap_err();
+
+        assert!(matches!(error, ApiError::ExternalApiError(_)), "{error}");
+        assert!(
+            error
+                .to_string()
+                .contains(&format!("exceeds {} bytes", BODY.len() - 1)),
+            "{error}"
+        );
+        assert_eq!(mock.calls("/v1/forecast"), 1);
+    }
+
+    #[tokio::test]
+    async fn a_body_at_the_limit_is_accepted() {
+        let mock = MockUpstream::start(|_| (StatusCode::OK, BODY.to_string())).await;
+        let upstream = limited_upstream(mock.url.clone(), BODY.len());
+
+        let body = fetch(&upstream).await.unwrap();
+
+        assert_eq!(body["latitude"], 52.52);
+    }
+
+    #[tokio::test]
+    async fn a_streamed_body_is_cut_off_once_it_passes_the_limit() {
+        let origin = serve_chunked(BODY).await;
+
+        let too_small = fetch(&limited_upstream(origin.clone(), 16)).await;
+        let large_enough = fetch(&limited_upstream(origin, BODY.len())).await;
+
+        let error = too_small.unwrap_err().to_string();
+        assert!(error.contains("exceeds 16 bytes"), "{error}");
+        assert_eq!(large_enough.unwrap()["longitude"], 13.41);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_once_the_budget_is_spent() {
+        let mock =
+            MockUpstream::start(|_| (StatusCode::INTERNAL_SERVER_ERROR, String::new())).await;
+        let upstream = upstream(&mock, 3.0);
+
+        for _ in 0..3 {
+            assert!(fetch(&upstream).await.is_err());
+        }
+
+        // Three attempts, then two (the last retry in the budget), then one.
+        assert_eq!(mock.calls("/v1/forecast"), 6);
+        assert_eq!(upstream.stats().retry_budget.available, 0.0);
+    }
+
+    #[test]
+    fn retry_budgets_refill_up_to_their_capacity() {
+        let budget = RetryBudget::new(1.0, 1000.0);
+
+        assert!(budget.try_spend());
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(budget.available(), 1.0);
+        assert!(budget.try_spend());
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let latencies: Vec<f64> = (1..=100).map(f64::from).collect();
+
+        assert_eq!(percentile(&latencies, 0.50), Some(50.0));
+        assert_eq!(percentile(&latencies, 0.95), Some(95.0));
+        assert_eq!(percentile(&latencies, 0.99), Some(99.0));
+        assert_eq!(percentile(&[7.0], 0.0), Some(7.0));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn latency_windows_report_percentiles_and_error_rate() {
+        let window = LatencyWindow::new(Duration::from_secs(60));
+        for ms in [10, 20, 30, 40] {
+            window.record(Duration::from_millis(ms), ms != 40);
+        }
+
+        let summary = window.summary();
+
+        assert_eq!(summary.samples, 4);
+        assert_eq!(summary.p50_ms, Some(20.0));
+        assert_eq!(summary.p99_ms, Some(40.0));
+        assert_eq!(summary.error_rate, 0.25);
+    }
+
+    #[test]
+    fn latency_windows_forget_samples_older_than_the_window() {
+        let window = LatencyWindow::new(Duration::from_millis(50));
+        window.record(Duration::from_millis(10), false);
+        std::thread::sleep(Duration::from_millis(100));
+        window.record(Duration::from_millis(20), true);
+
+        let summary = window.summary();
+
+        assert_eq!(summary.samples, 1);
+        assert_eq!(summary.p50_ms, Some(20.0));
+        assert_eq!(summary.error_rate, 0.0);
+    }
+
+    #[test]
+    fn latency_windows_keep_a_bounded_number_of_samples() {
+        let window = LatencyWindow::new(Duration::from_secs(60));
+        for _ in 0..MAX_SAMPLES + 5 {
+            window.record(Duration::from_millis(1), true);
+        }
+
+        assert_eq!(window.summary().samples, MAX_SAMPLES);
+    }
+}
@@ -0,0 +1,99 @@
+/// Optional hourly variables clients may request on top of `temperature_2m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HourlyVariable {
+    PrecipitationProbability,
+    UvIndex,
+    RelativeHumidity,
+    SurfacePressure,
+    DewPoint,
+}
+
+impl HourlyVariable {
+    pub const ALL: &'static [HourlyVariable] = &[
+        HourlyVariable::PrecipitationProbability,
+        HourlyVariable::UvIndex,
+        HourlyVariable::RelativeHumidity,
+        HourlyVariable::SurfacePressure,
+        HourlyVariable::DewPoint,
+    ];
+
+    /// The variable name as used by the Open-Meteo API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HourlyVariable::PrecipitationProbability => "precipitation_probability",
+            HourlyVariable::UvIndex => "uv_index",
+            HourlyVariable::RelativeHumidity => "relative_humidity_2m",
+            HourlyVariable::SurfacePressure => "surface_pressure",
+            HourlyVariable::DewPoint => "dew_point_2m",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|variable| variable.as_str() == name)
+    }
+
+    /// Parses a comma-separated list, rejecting anything not on the allowlist.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        let mut variables = Vec::new();
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let variable = Self::parse(name).ok_or_else(|| {
+                let allowed: Vec<&str> = Self::ALL.iter().map(|v| v.as_str()).collect();
+                format!(
+                    "Unknown variable {name:?}, expected one of: {}",
+                    allowed.join(", ")
+                )
+            })?;
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+        Ok(variables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_list_of_known_variables() {
+        assert_eq!(
+            HourlyVariable::parse_list(" precipitation_probability ,uv_index"),
+            Ok(vec![
+                HourlyVariable::PrecipitationProbability,
+                HourlyVariable::UvIndex
+            ])
+        );
+    }
+
+    #[test]
+    fn repeated_and_empty_entries_are_dropped() {
+        assert_eq!(
+            HourlyVariable::parse_list("precipitation_probability,,precipitation_probability"),
+            Ok(vec![HourlyVariable::PrecipitationProbability])
+        );
+        assert_eq!(HourlyVariable::parse_list(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn unknown_variables_are_rejected_with_the_allowed_names() {
+        let error = HourlyVariable::parse_list("precipitation_probability,wind_gusts").unwrap_err();
+
+        assert!(error.contains("\"wind_gusts\""), "{error}");
+        assert!(error.contains("precipitation_probability"), "{error}");
+    }
+
+    #[test]
+    fn every_variable_parses_from_its_api_name() {
+        for &variable in HourlyVariable::ALL {
+            assert_eq!(HourlyVariable::parse(variable.as_str()), Some(variable));
+        }
+    }
+}
@@ -0,0 +1,43 @@
+const form = document.getElementById("search");
+const message = document.getElementById("status");
+const current = document.getElementById("current");
+const table = document.getElementById("hourly");
+
+form.addEventListener("submit", async (event) => {
+    event.preventDefault();
+    const params = new URLSearchParams({
+        city: document.getElementById("city").value,
+        temperature_unit: document.getElementById("unit").value,
+    });
+
+    message.textContent = "Loading…";
+    current.textContent = "";
+    table.hidden = true;
+
+    const response = await fetch(`/weather?${params}`);
+    const body = await response.json();
+    if (!response.ok) {
+        message.textContent = body.error;
+        return;
+    }
+    message.textContent = "";
+
+    const unit = body.hourly_units.temperature_2m ?? "";
+    if (body.current) {
+        const icon = body.current.icon ? `${body.current.icon.emoji} ` : "";
+        current.textContent = `Now: ${icon}${body.current.temperature_2m} ${unit}`;
+    }
+    document.getElementById("temperature-header").textContent = `Temperature (${unit})`;
+
+    const rows = body.hourly.time.map((time, i) => {
+        const row = document.createElement("tr");
+        for (const value of [time, body.hourly.temperature_2m[i]]) {
+            const cell = document.createElement("td");
+            cell.textContent = value;
+            row.appendChild(cell);
+        }
+        return row;
+    });
+    table.querySelector("tbody").replaceChildren(...rows);
+    table.hidden = false;
+});